@@ -0,0 +1,289 @@
+//! Patch applier
+//! Applies hunks of parsed patch to lines of a file, the way GNU patch does:
+//! hunk is searched around the place its header says, moved by the offset of the
+//! previous hunk, and the nearest matching place wins. When it is not found anywhere,
+//! up to `fuzz` context lines at the beginning and the end of the hunk are ignored
+//! and the search is repeated. Hunks never overlap and are applied in order,
+//! hunk which is not found is reported as failed and the rest are still applied.
+//! Example:
+//!
+//!   let result = apply::apply(&lines, &patch.hunks, 2);
+//!   let failed = result.failed();
+//!
+//! `apply_text` applies hunks to the whole text of a file, keeping its line terminators.
+//!
+use std::cmp::min;
+
+use diff::hunk::{Hunk,LineKind,ScriptLine};
+
+
+/// Fuzz GNU patch uses, when not specified
+pub const DEFAULT_FUZZ : usize = 2;
+
+
+/// Outcome of single hunk
+/// Applied: `line` is 1 based line of the old file where the hunk was applied,
+/// `offset` how far it is from the line of its header and `fuzz` count of ignored context lines
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+pub enum HunkOutcome
+{
+    Applied { line : usize, offset : isize, fuzz : usize },
+    Failed,
+}
+
+
+/// Patched lines and outcome of every hunk, in order of hunks
+/// no_newline: Whether the last line is left without line terminator, given by the hunk
+/// applied at the end of the file. None when no hunk reaches it, the file keeps its own
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub struct ApplyResult
+{
+    pub lines      : Vec<String>,
+    pub outcomes   : Vec<HunkOutcome>,
+    pub no_newline : Option<bool>,
+}
+
+impl ApplyResult
+{
+    /// Indexes of hunks which were not applied
+    pub fn failed(&self) -> Vec<usize>
+    {
+        self.outcomes.iter().enumerate().filter(|(_, o)| **o == HunkOutcome::Failed).map(|(i, _)| i).collect()
+    }
+}
+
+
+/// Applies hunks to the lines, ignoring at most `fuzz` context lines at hunk edges
+pub fn apply<T : AsRef<str>>(lines : &[T], hunks : &[Hunk], fuzz : usize) -> ApplyResult
+{
+    let mut result = Vec::with_capacity(lines.len());
+    let mut outcomes = Vec::with_capacity(hunks.len());
+
+    let mut copied = 0;        //lines of the old file before this index are processed
+    let mut last_offset = 0;   //offset of previous applied hunk
+    let mut no_newline = None;
+
+    for hunk in hunks
+    {
+        let mut outcome = HunkOutcome::Failed;
+        for f in 0 ..= fuzz
+        {
+            let (lead, trail) = match trim(hunk, f) {
+                Some(t) => t,
+                None => break,
+            };
+
+            let body = &hunk.lines[lead .. hunk.lines.len() - trail];
+            let old_side : Vec<&str> = body.iter().filter(|l| l.kind != LineKind::Insert).map(|l| l.text.as_str()).collect();
+
+            let expected = hunk.old_start as isize + count_old(&hunk.lines[.. lead]) as isize;
+            if let Some(pos) = find(lines, &old_side, expected + last_offset, copied)
+            {
+                result.extend(lines[copied .. pos].iter().map(|l| l.as_ref().to_string()));
+                result.extend(body.iter().filter(|l| l.kind != LineKind::Remove).map(|l| l.text.clone()));
+                copied = pos + old_side.len();
+                if copied == lines.len() && trail == 0 {
+                    no_newline = Some(hunk.new_no_newline);
+                }
+
+                last_offset = pos as isize - expected;
+                outcome = HunkOutcome::Applied { line : pos + 1, offset : last_offset, fuzz : f };
+                break;
+            }
+        }
+        outcomes.push(outcome);
+    }
+
+    result.extend(lines[copied ..].iter().map(|l| l.as_ref().to_string()));
+    ApplyResult { lines : result, outcomes, no_newline }
+}
+
+
+/// Applies hunks to the text of a file and returns the patched text. Line terminator of the file,
+/// "\n" or "\r\n", is kept, so is a missing newline at the end, unless the hunk applied there changes it
+pub fn apply_text(text : &str, hunks : &[Hunk], fuzz : usize) -> (String, ApplyResult)
+{
+    let lines : Vec<&str> = text.lines().collect();
+    let result = apply(&lines, hunks, fuzz);
+
+    let crlf = text.find('\n').is_some_and(|i| text[.. i].ends_with('\r'));
+    let terminator = if crlf { "\r\n" } else { "\n" };
+    let at_end = match result.no_newline {
+        Some(no_newline) => !no_newline,
+        None => text.is_empty() || text.ends_with('\n'),
+    };
+
+    let mut patched = result.lines.join(terminator);
+    if at_end && !result.lines.is_empty() {
+        patched += terminator;
+    }
+
+    (patched, result)
+}
+
+
+///Count of context lines to ignore at the beginning and the end of hunk for given fuzz,
+///None when fuzz is larger than context of the hunk allows
+fn trim(hunk : &Hunk, fuzz : usize) -> Option<(usize, usize)>
+{
+    if fuzz == 0 {
+        return Some((0, 0));
+    }
+
+    let leading  = hunk.lines.iter().take_while(|l| l.kind == LineKind::Context).count();
+    let trailing = hunk.lines.iter().rev().take_while(|l| l.kind == LineKind::Context).count();
+    if leading == hunk.lines.len() || (leading < fuzz && trailing < fuzz) {
+        return None;
+    }
+
+    Some((min(leading, fuzz), min(trailing, fuzz)))
+}
+
+fn count_old(lines : &[ScriptLine]) -> usize
+{
+    lines.iter().filter(|l| l.kind != LineKind::Insert).count()
+}
+
+
+///Nearest position to `expected` (not before `min_pos`) where old side of the hunk matches
+fn find<T : AsRef<str>>(lines : &[T], old_side : &[&str], expected : isize, min_pos : usize) -> Option<usize>
+{
+    if lines.len() < old_side.len() {
+        return None;
+    }
+    let max_pos = (lines.len() - old_side.len()) as isize;
+    let min_pos = min_pos as isize;
+    if min_pos > max_pos {
+        return None;
+    }
+
+    let expected = expected.clamp(min_pos, max_pos);
+    let matches = |pos : isize| {
+        pos >= min_pos && pos <= max_pos
+            && old_side.iter().zip(&lines[pos as usize ..]).all(|(l1, l2)| *l1 == l2.as_ref())
+    };
+
+    let distance = std::cmp::max(expected - min_pos, max_pos - expected);
+    for d in 0 ..= distance
+    {
+        if matches(expected + d) {
+            return Some((expected + d) as usize);
+        }
+        if d > 0 && matches(expected - d) {
+            return Some((expected - d) as usize);
+        }
+    }
+
+    None
+}
+
+
+#[cfg(test)]
+mod tests
+{
+    use ::diff::apply::*;
+    use ::diff::hunk::{Hunk,LineKind,ScriptLine};
+    use ::diff::patch;
+
+    fn hunk(old_start : usize, spec : &str) -> Hunk
+    {
+        //" a" context, "-a" removed, "+a" inserted
+        let lines : Vec<ScriptLine> = spec.split('|').map(|l| {
+            let kind = match &l[.. 1] { "-" => LineKind::Remove, "+" => LineKind::Insert, _ => LineKind::Context };
+            ScriptLine::new(kind, &l[1 ..])
+        }).collect();
+        let old_count = lines.iter().filter(|l| l.kind != LineKind::Insert).count();
+        let new_count = lines.iter().filter(|l| l.kind != LineKind::Remove).count();
+        Hunk { old_start, old_count, new_start : old_start, new_count, lines, .. Hunk::default() }
+    }
+
+    #[test]
+    fn exact_place()
+    {
+        let lines = ["a", "b", "c", "d"];
+        let result = apply(&lines, &[hunk(0, " a|-b|+x| c"), hunk(3, " d|+e")], 0);
+
+        assert_eq!(result.lines, vec!["a", "x", "c", "d", "e"]);
+        assert_eq!(result.outcomes, vec![HunkOutcome::Applied { line : 1, offset : 0, fuzz : 0 }, HunkOutcome::Applied { line : 4, offset : 0, fuzz : 0 }]);
+    }
+
+    #[test]
+    fn offset()
+    {
+        let lines = ["new", "new", "a", "b", "c"];
+        let result = apply(&lines, &[hunk(0, " a|-b| c")], 0);
+
+        assert_eq!(result.lines, vec!["new", "new", "a", "c"]);
+        assert_eq!(result.outcomes, vec![HunkOutcome::Applied { line : 3, offset : 2, fuzz : 0 }]);
+    }
+
+    #[test]
+    fn fuzz()
+    {
+        let lines = ["A", "b", "c", "D"];
+        let h = hunk(0, " a|-b|+x| c| d");
+        assert_eq!(apply(&lines, std::slice::from_ref(&h), 0).failed(), vec![0]);
+
+        let result = apply(&lines, &[h], 1);
+        assert_eq!(result.lines, vec!["A", "x", "c", "D"]);
+        assert_eq!(result.outcomes, vec![HunkOutcome::Applied { line : 2, offset : 0, fuzz : 1 }]);
+    }
+
+    #[test]
+    fn failed_hunk_keeps_others()
+    {
+        let lines = ["a", "b", "c", "d"];
+        let result = apply(&lines, &[hunk(0, " a|-q| c"), hunk(2, " c|-d")], DEFAULT_FUZZ);
+
+        assert_eq!(result.lines, vec!["a", "b", "c"]);
+        assert_eq!(result.failed(), vec![0]);
+    }
+
+    #[test]
+    fn crlf_text()
+    {
+        let text = "a\r\nb\r\nc\r\n";
+        let (patched, result) = apply_text(text, &[hunk(0, " a|-b|+x|+y| c")], 0);
+
+        assert_eq!(result.failed(), Vec::<usize>::new());
+        assert_eq!(patched, "a\r\nx\r\ny\r\nc\r\n");
+    }
+
+    #[test]
+    fn missing_newline_at_end()
+    {
+        //the file keeps its missing newline when no hunk reaches the end
+        let (patched, _) = apply_text("a\nb\nc\nd", &[hunk(0, " a|-b|+x| c")], 0);
+        assert_eq!(patched, "a\nx\nc\nd");
+
+        //hunk at the end of the file adds the newline
+        let text = "--- a.txt\n+++ b.txt\n@@ -2,2 +2,2 @@\n b\n-c\n\\ No newline at end of file\n+c\n";
+        let patches = patch::parse(text).unwrap();
+        assert_eq!((patches[0].hunks[0].old_no_newline, patches[0].hunks[0].new_no_newline), (true, false));
+        let (patched, result) = apply_text("a\nb\nc", &patches[0].hunks, 0);
+        assert_eq!(result.no_newline, Some(false));
+        assert_eq!(patched, "a\nb\nc\n");
+
+        //and removes it, in reverse
+        let (patched, _) = apply_text(&patched, &[patches[0].hunks[0].inverted()], 0);
+        assert_eq!(patched, "a\nb\nc");
+
+        //context line without newline ends both sides, terminators of the file are kept
+        let text = "--- a.txt\n+++ b.txt\n@@ -1,2 +1,3 @@\n a\n+x\n b\n\\ No newline at end of file\n";
+        let patches = patch::parse(text).unwrap();
+        let (patched, _) = apply_text("a\r\nb", &patches[0].hunks, 0);
+        assert_eq!(patched, "a\r\nx\r\nb");
+    }
+
+    #[test]
+    fn reverse()
+    {
+        let lines = ["a", "x", "c"];
+        let result = apply(&lines, &[hunk(0, " a|-b|+x| c").inverted()], 0);
+
+        assert_eq!(result.lines, vec!["a", "b", "c"]);
+    }
+}
@@ -0,0 +1,228 @@
+//! Bit-parallel edit distance
+//! Implementation of Gene Myers' bit-vector algorithm (1999), in the formulation of Heikki Hyyrö
+//! for the distance of whole strings. Column of the Levenshtein matrix is kept as 2 bit vectors
+//! of vertical +1 and -1 deltas, so the whole column is computed by few word operations.
+//! Paper source: https://doi.org/10.1145/316542.316550
+//! Only unit costs of insert, remove and update are supported, without transposition.
+//! The shorter string has to fit into the word: up to 128 characters.
+use std::ops::{BitAnd,BitOr,BitXor,Not,Shl};
+
+
+/// Longest shorter string the distance can be computed for
+pub const MAX_LENGTH : usize = 128;
+
+
+///Unsigned integer used as bit vector
+trait BitVector : Copy + PartialEq + BitAnd<Output = Self> + BitOr<Output = Self> + BitXor<Output = Self> + Not<Output = Self> + Shl<usize, Output = Self>
+{
+    const ZERO : Self;
+    const ONE  : Self;
+
+    fn wrapping_add(self, other : Self) -> Self;
+}
+
+impl BitVector for u64
+{
+    const ZERO : u64 = 0;
+    const ONE  : u64 = 1;
+
+    fn wrapping_add(self, other : u64) -> u64
+    {
+        u64::wrapping_add(self, other)
+    }
+}
+
+impl BitVector for u128
+{
+    const ZERO : u128 = 0;
+    const ONE  : u128 = 1;
+
+    fn wrapping_add(self, other : u128) -> u128
+    {
+        u128::wrapping_add(self, other)
+    }
+}
+
+
+/// Levenshtein distance with unit costs, None when both strings are longer than MAX_LENGTH
+pub fn distance(_old : &[char], _new : &[char]) -> Option<u32>
+{
+    //distance is symmetric, the shorter string is the one kept in bit vectors
+    let (pattern, text) = if _old.len() <= _new.len() { (_old, _new) } else { (_new, _old) };
+
+    if pattern.is_empty() {
+        Some(text.len() as u32)
+    }
+    else if pattern.len() <= 64 {
+        Some(compute::<u64>(pattern, text))
+    }
+    else if pattern.len() <= MAX_LENGTH {
+        Some(compute::<u128>(pattern, text))
+    }
+    else {
+        None
+    }
+}
+
+
+fn compute<W : BitVector>(pattern : &[char], text : &[char]) -> u32
+{
+    let masks = PatternMasks::<W>::new(pattern);
+    let last  = W::ONE << (pattern.len() - 1);
+
+    let mut pv    = !W::ZERO; //vertical +1 deltas, first column is 0, 1, 2 ..
+    let mut mv    = W::ZERO;  //vertical -1 deltas
+    let mut score = pattern.len() as u32;
+
+    for &ch in text
+    {
+        let eq = masks.get(ch);
+        let xv = eq | mv;
+        let xh = ((eq & pv).wrapping_add(pv) ^ pv) | eq;
+
+        let mut ph = mv | !(xh | pv);
+        let mut mh = pv & xh;
+        if ph & last != W::ZERO {
+            score += 1;
+        }
+        else if mh & last != W::ZERO {
+            score -= 1;
+        }
+
+        //first row grows by 1 in every column
+        ph = (ph << 1) | W::ONE;
+        mh = mh << 1;
+        pv = mh | !(xv | ph);
+        mv = ph & xv;
+    }
+
+    score
+}
+
+
+///Bit masks of positions of characters in the pattern
+struct PatternMasks<W>
+{
+    ascii : [W; 128],
+    other : Vec<(char, W)>,
+}
+
+impl<W : BitVector> PatternMasks<W>
+{
+    fn new(pattern : &[char]) -> PatternMasks<W>
+    {
+        let mut masks = PatternMasks { ascii : [W::ZERO; 128], other : Vec::new() };
+        for (i, &ch) in pattern.iter().enumerate()
+        {
+            let bit = W::ONE << i;
+            if ch.is_ascii() {
+                masks.ascii[ch as usize] = masks.ascii[ch as usize] | bit;
+            }
+            else {
+                match masks.other.iter_mut().find(|(c, _)| *c == ch) {
+                    Some(entry) => entry.1 = entry.1 | bit,
+                    None => masks.other.push((ch, bit)),
+                }
+            }
+        }
+
+        masks
+    }
+
+    fn get(&self, ch : char) -> W
+    {
+        if ch.is_ascii() {
+            self.ascii[ch as usize]
+        }
+        else {
+            self.other.iter().find(|(c, _)| *c == ch).map_or(W::ZERO, |(_, mask)| *mask)
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests
+{
+    extern crate quickcheck;
+
+    use std::cell::RefCell;
+    use ::diff::bitparallel::*;
+    use ::diff::difflib::{StringDiff,DiffCosts};
+    use self::quickcheck::{QuickCheck,TestResult};
+
+    fn chars(s : &str) -> Vec<char>
+    {
+        s.chars().collect()
+    }
+
+    thread_local! {
+        //transposition costs as much as 2 updates, so the distance is the same,
+        //but StringDiff computes it by the matrix
+        static MATRIX : RefCell<StringDiff> = RefCell::new(StringDiff::with_costs(DiffCosts { transpose : Some(2), .. DiffCosts::default() }));
+    }
+
+    ///Distance computed by the matrix
+    fn matrix_distance(_old : &str, _new : &str) -> u32
+    {
+        MATRIX.with(|sd| sd.borrow_mut().distance(_old, _new))
+    }
+
+    #[test]
+    fn known_distances()
+    {
+        assert_eq!(distance(&chars("kitten"), &chars("sitting")), Some(3));
+        assert_eq!(distance(&chars(""), &chars("abc")), Some(3));
+        assert_eq!(distance(&chars("abc"), &chars("")), Some(3));
+        assert_eq!(distance(&chars("flaw"), &chars("lawn")), Some(2));
+        assert_eq!(distance(&chars("դա կատարյալ է"), &chars("դա կատարյալ  ")), Some(1));
+
+        let long : String = "x".repeat(MAX_LENGTH + 1);
+        assert_eq!(distance(&chars(&long), &chars(&long)), None);
+        assert_eq!(distance(&chars(&long), &chars("x")), Some(MAX_LENGTH as u32));
+
+        //StringDiff uses the bit vectors with default costs
+        assert_eq!(StringDiff::new().distance("kitten", "sitting"), 3);
+        assert_eq!(StringDiff::new().distance(&long, &"y".repeat(MAX_LENGTH + 1)), MAX_LENGTH as u32 + 1);
+    }
+
+    #[test]
+    fn word_boundaries()
+    {
+        //patterns of 64 characters use the whole u64, longer ones u128
+        for len in [63, 64, 65, 127, 128].iter()
+        {
+            let _old : String = "ab".repeat(64)[.. *len].to_string();
+            let _new : String = format!("b{}c", &_old[.. *len - 1]);
+            assert_eq!(distance(&chars(&_old), &chars(&_new)), Some(matrix_distance(&_old, &_new)), "{}", len);
+        }
+    }
+
+    #[test]
+    fn same_as_matrix()
+    {
+        fn property(_old : String, _new : String) -> TestResult
+        {
+            if _old.chars().count() > MAX_LENGTH && _new.chars().count() > MAX_LENGTH {
+                return TestResult::discard();
+            }
+            TestResult::from_bool(distance(&chars(&_old), &chars(&_new)) == Some(matrix_distance(&_old, &_new)))
+        }
+
+        QuickCheck::new().tests(500).quickcheck(property as fn(String, String) -> TestResult);
+    }
+
+    #[test]
+    fn same_as_matrix_small_alphabet()
+    {
+        //random strings rarely share characters, these do
+        fn property(_old : Vec<bool>, _new : Vec<u8>) -> bool
+        {
+            let _old : String = _old.iter().map(|&b| if b { 'a' } else { 'b' }).collect();
+            let _new : String = _new.iter().map(|&b| (b'a' + b % 3) as char).collect();
+            distance(&chars(&_old), &chars(&_new)) == Some(matrix_distance(&_old, &_new))
+        }
+
+        QuickCheck::new().tests(500).quickcheck(property as fn(Vec<bool>, Vec<u8>) -> bool);
+    }
+}
@@ -0,0 +1,274 @@
+//! Brief comparison: reports only whether inputs differ
+//! Inputs are compared byte by byte and comparison stops at the first difference,
+//! no edit script is computed. Directories are compared recursively, by relative
+//! paths of their files. File compared with directory is compared with the file
+//! of the same name in the directory. Messages follow `diff -q`:
+//!
+//!   Files a/x.txt and b/x.txt differ
+//!   Files a/y.txt and b/y.txt are identical    (only reported on request)
+//!   Only in a/sub: z.txt
+//!
+use std;
+use std::fmt;
+use std::fs;
+use std::io::{Read,BufReader};
+use std::path::{Path,PathBuf};
+
+use diff::parallel;
+
+
+/// Result of brief comparison of single entry of compared directories, or of 2 files
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+pub enum BriefResult
+{
+    Differ(PathBuf, PathBuf),
+    Identical(PathBuf, PathBuf),
+    OnlyIn(PathBuf, String),
+}
+
+impl BriefResult
+{
+    /// True for any difference: different content, or entry missing at one side
+    pub fn is_difference(&self) -> bool
+    {
+        !matches!(*self, BriefResult::Identical(..))
+    }
+}
+
+impl fmt::Display for BriefResult
+{
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result
+    {
+        match *self
+        {
+            BriefResult::Differ(ref p1, ref p2)    => write!(f, "Files {} and {} differ", p1.display(), p2.display()),
+            BriefResult::Identical(ref p1, ref p2) => write!(f, "Files {} and {} are identical", p1.display(), p2.display()),
+            BriefResult::OnlyIn(ref dir, ref name) => write!(f, "Only in {}: {}", dir.display(), name),
+        }
+    }
+}
+
+
+/// Compares 2 streams, stops reading at the first different byte
+pub fn streams_differ<R1 : Read, R2 : Read>(_old : R1, _new : R2) -> std::io::Result<bool>
+{
+    let mut bytes1 = BufReader::new(_old).bytes();
+    let mut bytes2 = BufReader::new(_new).bytes();
+
+    loop
+    {
+        match (bytes1.next(), bytes2.next())
+        {
+            (None, None) => return Ok(false),
+            (Some(b1), Some(b2)) => {
+                if b1? != b2? {
+                    return Ok(true);
+                }
+            },
+            (Some(b), None) | (None, Some(b)) => { b?; return Ok(true) },
+        }
+    }
+}
+
+
+/// Compares 2 files, files of different size are reported without reading them
+pub fn files_differ(_old : &Path, _new : &Path) -> std::io::Result<bool>
+{
+    let (meta1, meta2) = (fs::metadata(_old)?, fs::metadata(_new)?);
+    if meta1.is_file() && meta2.is_file() && meta1.len() != meta2.len() {
+        return Ok(true);
+    }
+
+    streams_differ(fs::File::open(_old)?, fs::File::open(_new)?)
+}
+
+
+/// Paths of compared files: directory compared with a file stands for the file of the same name
+/// in the directory, as in diff. Other paths are kept
+pub fn resolve_paths(_old : &Path, _new : &Path) -> (PathBuf, PathBuf)
+{
+    match (_old.is_dir(), _new.is_dir(), _old.file_name(), _new.file_name())
+    {
+        (true, false, _, Some(name)) => (_old.join(name), _new.to_path_buf()),
+        (false, true, Some(name), _) => (_old.to_path_buf(), _new.join(name)),
+        _ => (_old.to_path_buf(), _new.to_path_buf()),
+    }
+}
+
+
+/// Compares 2 files or 2 directories recursively. Entries are reported in order of their names,
+/// identical files are reported as well, callers filter them out if they are not interesting
+#[allow(dead_code)]
+pub fn compare(_old : &Path, _new : &Path) -> std::io::Result<Vec<BriefResult>>
+{
+    compare_parallel(_old, _new, 1)
+}
+
+
+/// Same as compare, files of directories are compared on `threads` threads.
+/// Order of entries does not depend on count of threads
+pub fn compare_parallel(_old : &Path, _new : &Path, threads : usize) -> std::io::Result<Vec<BriefResult>>
+{
+    if !(_old.is_dir() && _new.is_dir()) 
+    {
+        let (p1, p2) = resolve_paths(_old, _new);
+        return Ok(vec![compare_files(&p1, &p2)?]);
+    }
+
+    let entries = dir_entries(_old, _new)?;
+    parallel::map(&entries, threads, || (), |_, entry| {
+        match *entry
+        {
+            Entry::Done(ref r) => Ok(r.clone()),
+            Entry::Files(ref p1, ref p2) => compare_files(p1, p2),
+        }
+    }).into_iter().collect()
+}
+
+
+/// Entry of compared directories: known result, or pair of files to compare
+pub enum Entry
+{
+    Done(BriefResult),
+    Files(PathBuf, PathBuf),
+}
+
+
+/// Entries of 2 directories compared recursively, in order of their names. Files present at both sides
+/// are left to compare, entries present at one side only are reported, as compare reports them
+pub fn dir_entries(_old : &Path, _new : &Path) -> std::io::Result<Vec<Entry>>
+{
+    let mut entries = Vec::new();
+    compare_dirs(_old, _new, &mut entries)?;
+
+    Ok(entries)
+}
+
+
+fn compare_files(_old : &Path, _new : &Path) -> std::io::Result<BriefResult>
+{
+    let (p1, p2) = (_old.to_path_buf(), _new.to_path_buf());
+    if files_differ(_old, _new)? { Ok(BriefResult::Differ(p1, p2)) } else { Ok(BriefResult::Identical(p1, p2)) }
+}
+
+
+///Collects entries of both directories, files present at both sides are left to compare
+fn compare_dirs(_old : &Path, _new : &Path, result : &mut Vec<Entry>) -> std::io::Result<()>
+{
+    let names1 = entry_names(_old)?;
+    let names2 = entry_names(_new)?;
+
+    //both lists are sorted, merge them
+    let (mut i, mut j) = (0, 0);
+    while i < names1.len() || j < names2.len()
+    {
+        if j == names2.len() || (i < names1.len() && names1[i] < names2[j]) {
+            result.push(Entry::Done(BriefResult::OnlyIn(_old.to_path_buf(), names1[i].clone())));
+            i += 1;
+        }
+        else if i == names1.len() || names2[j] < names1[i] {
+            result.push(Entry::Done(BriefResult::OnlyIn(_new.to_path_buf(), names2[j].clone())));
+            j += 1;
+        }
+        else {
+            let (p1, p2) = (_old.join(&names1[i]), _new.join(&names2[j]));
+            match (p1.is_dir(), p2.is_dir())
+            {
+                (true, true)   => compare_dirs(&p1, &p2, result)?,
+                (false, false) => result.push(Entry::Files(p1, p2)),
+                //file and directory of the same name can not be identical
+                _ => result.push(Entry::Done(BriefResult::Differ(p1, p2))),
+            }
+            i += 1;
+            j += 1;
+        }
+    }
+
+    Ok(())
+}
+
+
+///Sorted names of directory entries
+fn entry_names(dir : &Path) -> std::io::Result<Vec<String>>
+{
+    let mut names = Vec::new();
+    for entry in fs::read_dir(dir)?
+    {
+        names.push(entry?.file_name().to_string_lossy().into_owned());
+    }
+    names.sort();
+
+    Ok(names)
+}
+
+
+#[cfg(test)]
+mod tests
+{
+    use ::diff::brief::*;
+    use std::fs;
+    use std::env;
+
+    #[test]
+    fn streams()
+    {
+        assert!(!streams_differ("same text".as_bytes(), "same text".as_bytes()).unwrap());
+        assert!(streams_differ("same text".as_bytes(), "same test".as_bytes()).unwrap());
+        assert!(streams_differ("prefix".as_bytes(), "prefix and more".as_bytes()).unwrap());
+        assert!(!streams_differ("".as_bytes(), "".as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn directories()
+    {
+        let root = env::temp_dir().join(format!("diff_brief_test_{}", std::process::id()));
+        let (dir1, dir2) = (root.join("a"), root.join("b"));
+        fs::create_dir_all(dir1.join("sub")).unwrap();
+        fs::create_dir_all(dir2.join("sub")).unwrap();
+
+        fs::write(dir1.join("same.txt"), "same").unwrap();
+        fs::write(dir2.join("same.txt"), "same").unwrap();
+        fs::write(dir1.join("sub").join("changed.txt"), "old").unwrap();
+        fs::write(dir2.join("sub").join("changed.txt"), "new").unwrap();
+        fs::write(dir1.join("removed.txt"), "").unwrap();
+        fs::write(dir2.join("sub").join("added.txt"), "").unwrap();
+
+        let result = compare(&dir1, &dir2);
+        let parallel_result = compare_parallel(&dir1, &dir2, 3);
+        fs::remove_dir_all(&root).unwrap();
+
+        let result = result.unwrap();
+        assert_eq!(parallel_result.unwrap(), result);
+        assert_eq!(result, vec![
+            BriefResult::OnlyIn(dir1.clone(), String::from("removed.txt")),
+            BriefResult::Identical(dir1.join("same.txt"), dir2.join("same.txt")),
+            BriefResult::OnlyIn(dir2.join("sub"), String::from("added.txt")),
+            BriefResult::Differ(dir1.join("sub").join("changed.txt"), dir2.join("sub").join("changed.txt")),
+        ]);
+        assert_eq!(result.iter().filter(|r| r.is_difference()).count(), 3);
+        assert_eq!(format!("{}", result[0]), format!("Only in {}: removed.txt", dir1.display()));
+    }
+
+    #[test]
+    fn file_and_directory()
+    {
+        let root = env::temp_dir().join(format!("diff_brief_file_dir_{}", std::process::id()));
+        let dir = root.join("dir");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(root.join("x.txt"), "old").unwrap();
+        fs::write(dir.join("x.txt"), "new").unwrap();
+        fs::write(root.join("y.txt"), "").unwrap();
+
+        //file is compared with the file of the same name in the directory, at either side
+        let forward  = compare(&root.join("x.txt"), &dir);
+        let backward = compare(&dir, &root.join("x.txt"));
+        let missing  = compare(&root.join("y.txt"), &dir);
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(forward.unwrap(), vec![BriefResult::Differ(root.join("x.txt"), dir.join("x.txt"))]);
+        assert_eq!(backward.unwrap(), vec![BriefResult::Differ(dir.join("x.txt"), root.join("x.txt"))]);
+        assert!(missing.is_err());
+    }
+}
@@ -0,0 +1,358 @@
+//! Compaction of line edit script
+//! Inserted or removed block surrounded by unchanged lines often can be placed
+//! in several positions producing equally short script, e.g. new function which
+//! closing brace matches either of two braces.
+//! Compaction slides such blocks to the position that starts and ends at
+//! blank lines or indentation changes, so the script reads naturally.
+//! Scoring is a port of git's indent heuristic (xdiff/xdiffi.c).
+use diff::linediff::LineCell;
+use diff::difflib::DiffOperation;
+
+
+const MAX_INDENT  : i32 = 200; //indentation above this is treated as MAX_INDENT
+const MAX_BLANKS  : i32 = 20;  //blank lines counted in each direction
+const MAX_SLIDING : usize = 100; //max distance the block is slided to
+
+const START_OF_FILE_PENALTY               : i32 = 1;
+const END_OF_FILE_PENALTY                 : i32 = 21;
+const TOTAL_BLANK_WEIGHT                  : i32 = -30;
+const POST_BLANK_WEIGHT                   : i32 = 6;
+const RELATIVE_INDENT_PENALTY             : i32 = -4;
+const RELATIVE_INDENT_WITH_BLANK_PENALTY  : i32 = 10;
+const RELATIVE_OUTDENT_PENALTY            : i32 = 24;
+const RELATIVE_OUTDENT_WITH_BLANK_PENALTY : i32 = 17;
+const RELATIVE_DEDENT_PENALTY             : i32 = 23;
+const RELATIVE_DEDENT_WITH_BLANK_PENALTY  : i32 = 17;
+const INDENT_WEIGHT                       : i32 = 60;
+
+
+///Characteristics of the place between two lines where the block would start or end
+struct SplitMeasure
+{
+    end_of_file : bool,
+    indent      : i32,  //indent of the line after the split, -1 for blank line
+    pre_blank   : i32,  //blank lines before the split
+    pre_indent  : i32,  //indent of the first non blank line before the split
+    post_blank  : i32,  //blank lines after the line following the split
+    post_indent : i32   //indent of the first non blank line after that
+}
+
+///Accumulated score of the block position, lower is better
+#[derive(Default)]
+struct SplitScore
+{
+    effective_indent : i32,
+    penalty          : i32
+}
+
+
+/// Slides every inserted or removed block of the script, which is surrounded by
+/// unchanged lines, to the best scored position. Script stays valid and of the same size.
+pub fn compact<T : AsRef<str>>(script : &mut Vec<LineCell>, _old : &[T], _new : &[T])
+{
+    //empty unchanged blocks on both ends, so every block has neighbours to exchange lines with
+    script.insert(0, LineCell{old_start: 0, new_start: 0, count: 0, operation: DiffOperation::None});
+    script.push(LineCell{old_start: _old.len(), new_start: _new.len(), count: 0, operation: DiffOperation::None});
+
+    for i in 1 .. script.len() - 1
+    {
+        if script[i - 1].operation != DiffOperation::None || script[i + 1].operation != DiffOperation::None {
+            continue; //part of a replacement, nothing to slide
+        }
+
+        let shift = match script[i].operation
+        {
+            DiffOperation::Insert => best_shift(script, i, _new, script[i].new_start),
+            DiffOperation::Remove => best_shift(script, i, _old, script[i].old_start),
+            _ => 0,
+        };
+
+        if shift != 0 {
+            apply_shift(script, i, shift);
+        }
+    }
+
+    script.retain(|cell| cell.count > 0);
+}
+
+
+///Finds the best offset for the block at position `idx` of the script,
+///`lines` is the sequence the block belongs to and `start` its index in it
+fn best_shift<T : AsRef<str>>(script : &[LineCell], idx : usize, lines : &[T], start : usize) -> isize
+{
+    let count  = script[idx].count;
+    let before = script[idx - 1].count;
+    let after  = script[idx + 1].count;
+
+    //how far block can go UP
+    let mut up = 0;
+    while up < before && up < MAX_SLIDING
+            && lines[start - up - 1].as_ref() == lines[start + count - up - 1].as_ref()
+    {
+        up += 1;
+    }
+
+    //how far block can go DOWN
+    let mut down = 0;
+    while down < after && down < MAX_SLIDING
+            && lines[start + down].as_ref() == lines[start + count + down].as_ref()
+    {
+        down += 1;
+    }
+
+    if up == 0 && down == 0 {
+        return 0;
+    }
+
+    //evaluate every position from the top down, on equal scores the lowest one wins (as in git)
+    let mut best : Option<(isize, SplitScore)> = None;
+    for pos in (start - up) ..= (start + down)
+    {
+        let mut score = SplitScore::default();
+        score_add_split(&measure_split(lines, pos + count), &mut score);
+        score_add_split(&measure_split(lines, pos), &mut score);
+
+        let better = match best {
+            Some((_, ref best_score)) => score_cmp(&score, best_score) <= 0,
+            None => true,
+        };
+        if better {
+            best = Some((pos as isize - start as isize, score));
+        }
+    }
+
+    best.map_or(0, |(shift, _)| shift)
+}
+
+
+///Moves the block at position `idx` by `shift` lines,
+///unchanged blocks around it exchange the lines the block passed over
+fn apply_shift(script : &mut [LineCell], idx : usize, shift : isize)
+{
+    let delta = shift.unsigned_abs();
+    if shift < 0
+    {
+        script[idx - 1].count -= delta;
+        script[idx].old_start -= delta;
+        script[idx].new_start -= delta;
+        script[idx + 1].old_start -= delta;
+        script[idx + 1].new_start -= delta;
+        script[idx + 1].count += delta;
+    }
+    else
+    {
+        script[idx - 1].count += delta;
+        script[idx].old_start += delta;
+        script[idx].new_start += delta;
+        script[idx + 1].old_start += delta;
+        script[idx + 1].new_start += delta;
+        script[idx + 1].count -= delta;
+    }
+}
+
+
+///Returns indentation of the line: spaces count 1 and tabs move to the next multiple of 8.
+///Blank line has indentation of -1.
+fn get_indent(line : &str) -> i32
+{
+    let mut ret = 0;
+    for ch in line.chars()
+    {
+        if !ch.is_whitespace() {
+            return ret;
+        }
+        else if ch == ' ' {
+            ret += 1;
+        }
+        else if ch == '\t' {
+            ret += 8 - ret % 8;
+        }
+        //other whitespace is ignored
+
+        if ret >= MAX_INDENT {
+            return MAX_INDENT;
+        }
+    }
+
+    -1
+}
+
+
+///Measures the place just before the line `split`
+fn measure_split<T : AsRef<str>>(lines : &[T], split : usize) -> SplitMeasure
+{
+    let mut m = SplitMeasure {
+        end_of_file : split >= lines.len(),
+        indent      : -1,
+        pre_blank   : 0,
+        pre_indent  : -1,
+        post_blank  : 0,
+        post_indent : -1
+    };
+
+    if !m.end_of_file {
+        m.indent = get_indent(lines[split].as_ref());
+    }
+
+    for line in lines[.. split.min(lines.len())].iter().rev()
+    {
+        let indent = get_indent(line.as_ref());
+        if indent != -1 {
+            m.pre_indent = indent;
+            break;
+        }
+        m.pre_blank += 1;
+        if m.pre_blank == MAX_BLANKS {
+            m.pre_indent = 0;
+            break;
+        }
+    }
+
+    if split + 1 < lines.len()
+    {
+        for line in lines[split + 1 ..].iter()
+        {
+            let indent = get_indent(line.as_ref());
+            if indent != -1 {
+                m.post_indent = indent;
+                break;
+            }
+            m.post_blank += 1;
+            if m.post_blank == MAX_BLANKS {
+                m.post_indent = 0;
+                break;
+            }
+        }
+    }
+
+    m
+}
+
+
+///Adds penalty of the split to the score
+fn score_add_split(m : &SplitMeasure, s : &mut SplitScore)
+{
+    if m.pre_indent == -1 && m.pre_blank == 0 {
+        s.penalty += START_OF_FILE_PENALTY;
+    }
+
+    if m.end_of_file {
+        s.penalty += END_OF_FILE_PENALTY;
+    }
+
+    //blank lines after the split, line following the split included
+    let post_blank  = if m.indent == -1 { 1 + m.post_blank } else { 0 };
+    let total_blank = m.pre_blank + post_blank;
+
+    s.penalty += TOTAL_BLANK_WEIGHT * total_blank;
+    s.penalty += POST_BLANK_WEIGHT * post_blank;
+
+    let indent = if m.indent != -1 { m.indent } else { m.post_indent };
+    let any_blanks = total_blank != 0;
+
+    s.effective_indent += indent;
+
+    if indent == -1 || m.pre_indent == -1 {
+        //no adjustments
+    }
+    else if indent > m.pre_indent {
+        //line is indented more than its predecessor
+        s.penalty += if any_blanks { RELATIVE_INDENT_WITH_BLANK_PENALTY } else { RELATIVE_INDENT_PENALTY };
+    }
+    else if indent == m.pre_indent {
+        //same indentation, no adjustments
+    }
+    else if m.post_indent != -1 && m.post_indent > indent {
+        //line is indented less than its predecessor, but followed by more indented one
+        s.penalty += if any_blanks { RELATIVE_OUTDENT_WITH_BLANK_PENALTY } else { RELATIVE_OUTDENT_PENALTY };
+    }
+    else {
+        //line is indented less than its predecessor and not followed by more indented one
+        s.penalty += if any_blanks { RELATIVE_DEDENT_WITH_BLANK_PENALTY } else { RELATIVE_DEDENT_PENALTY };
+    }
+}
+
+
+///Compares scores, negative result means `s1` is better
+fn score_cmp(s1 : &SplitScore, s2 : &SplitScore) -> i32
+{
+    let cmp_indents = (s1.effective_indent > s2.effective_indent) as i32 - (s1.effective_indent < s2.effective_indent) as i32;
+    INDENT_WEIGHT * cmp_indents + (s1.penalty - s2.penalty)
+}
+
+
+#[cfg(test)]
+mod tests
+{
+    use ::diff::linediff::*;
+    use ::diff::difflib::DiffOperation;
+    use ::diff::compaction::compact;
+
+    #[test]
+    fn insert_block_starts_at_opening_line()
+    {
+        let _old = vec!["if a {", "    x();", "}", "", "if a {", "    y();", "}"];
+        let _new = vec!["if a {", "    x();", "}", "", "if a {", "    z();", "}", "", "if a {", "    y();", "}"];
+        let mut ld = LineDiff::new();
+        let script = ld.get_diff(&_old, &_new);
+
+        assert_eq!(script.len(), 3);
+        assert_eq!(script[1], LineCell{old_start: 4, new_start: 4, count: 4, operation: DiffOperation::Insert});
+    }
+
+    #[test]
+    fn remove_block_starts_at_opening_line()
+    {
+        let _old = vec!["if a {", "    x();", "}", "", "if a {", "    z();", "}", "", "if a {", "    y();", "}"];
+        let _new = vec!["if a {", "    x();", "}", "", "if a {", "    y();", "}"];
+        let mut ld = LineDiff::new();
+        let script = ld.get_diff(&_old, &_new);
+
+        assert_eq!(script.len(), 3);
+        assert_eq!(script[1], LineCell{old_start: 4, new_start: 4, count: 4, operation: DiffOperation::Remove});
+    }
+
+    #[test]
+    fn insert_nested_block()
+    {
+        let _old = vec!["fn a() {", "    if x {", "        x();", "    }", "}"];
+        let _new = vec!["fn a() {", "    if x {", "        x();", "    }", "    if x {", "        x();", "    }", "}"];
+        let mut ld = LineDiff::new();
+        let script = ld.get_diff(&_old, &_new);
+
+        assert_eq!(script.len(), 3);
+        assert_eq!(script[1], LineCell{old_start: 4, new_start: 4, count: 3, operation: DiffOperation::Insert});
+    }
+
+    #[test]
+    fn replacement_is_not_slided()
+    {
+        let _old = vec!["a", "b", "a", "c"];
+        let _new = vec!["a", "x", "c"];
+        let mut ld = LineDiff::new();
+        let script = ld.get_diff(&_old, &_new);
+
+        assert_eq!(script[0], LineCell{old_start: 0, new_start: 0, count: 1, operation: DiffOperation::None});
+        assert_eq!(script[1].operation, DiffOperation::Remove);
+        assert_eq!(script[2].operation, DiffOperation::Insert);
+    }
+
+    #[test]
+    fn lowest_position_wins_on_equal_scores()
+    {
+        let _old = vec!["x", "a", "b", "y"];
+        let _new = vec!["x", "a", "b", "a", "b", "y"];
+        let mut script = vec![
+            LineCell{old_start: 0, new_start: 0, count: 1, operation: DiffOperation::None},
+            LineCell{old_start: 1, new_start: 1, count: 2, operation: DiffOperation::Insert},
+            LineCell{old_start: 1, new_start: 3, count: 3, operation: DiffOperation::None},
+        ];
+        compact(&mut script, &_old, &_new);
+
+        assert_eq!(script, vec![
+            LineCell{old_start: 0, new_start: 0, count: 3, operation: DiffOperation::None},
+            LineCell{old_start: 3, new_start: 3, count: 2, operation: DiffOperation::Insert},
+            LineCell{old_start: 3, new_start: 5, count: 1, operation: DiffOperation::None},
+        ]);
+    }
+}
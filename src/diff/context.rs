@@ -0,0 +1,207 @@
+//! Context format presenter, as `diff -c` writes it:
+//!
+//!     *** a.txt
+//!     --- b.txt
+//!     ***************
+//!     *** 1,3 ****
+//!       unchanged
+//!     ! changed
+//!     - removed
+//!     --- 1,3 ----
+//!       unchanged
+//!     ! modified
+//!     + inserted
+//!
+//! Lines of a change having both removed and inserted lines are marked with "!",
+//! section without any change is omitted. Hunks are the same as of unified format.
+use std;
+use std::io::Write;
+
+use diff::difflib::DiffCell;
+use diff::hunk::{Hunk,HunkRecorder,LineKind};
+use diff::presenter::Presenter;
+use diff::unified::NO_NEWLINE;
+
+
+pub struct ContextPresenter
+{
+    writer   : Box<dyn std::io::Write>,
+    context  : usize,
+    recorder : HunkRecorder,
+}
+
+impl ContextPresenter
+{
+    pub fn new(_w : Box<dyn std::io::Write>, context : usize) -> ContextPresenter
+    {
+        ContextPresenter { writer : _w, context, recorder : HunkRecorder::new() }
+    }
+
+    ///Writes one side of the hunk, `kind` is the kind of changed lines of this side
+    fn write_section(&mut self, hunk : &Hunk, kind : LineKind) -> std::result::Result<usize, std::io::Error>
+    {
+        //marker follows the last line of the side, when it has no line terminator
+        let no_newline = if kind == LineKind::Remove { hunk.old_no_newline } else { hunk.new_no_newline };
+        let last = hunk.lines.iter().rposition(|l| l.kind == kind || l.kind == LineKind::Context).filter(|_| no_newline);
+
+        let mut bytes_written = 0;
+        let mut i = 0;
+        while i < hunk.lines.len()
+        {
+            if hunk.lines[i].kind == LineKind::Context
+            {
+                bytes_written += self.writer.write(format!("  {}\n", hunk.lines[i].text).as_bytes())?;
+                if last == Some(i) {
+                    bytes_written += self.writer.write(NO_NEWLINE.as_bytes())?;
+                }
+                i += 1;
+                continue;
+            }
+
+            //change: consecutive removed and inserted lines
+            let end = hunk.lines[i ..].iter().position(|l| l.kind == LineKind::Context).map_or(hunk.lines.len(), |p| i + p);
+            let change = &hunk.lines[i .. end];
+            let marker = if change.iter().any(|l| l.kind != kind) {
+                '!'
+            }
+            else if kind == LineKind::Remove {
+                '-'
+            }
+            else {
+                '+'
+            };
+
+            for (j, line) in change.iter().enumerate().filter(|(_, l)| l.kind == kind)
+            {
+                bytes_written += self.writer.write(format!("{} {}\n", marker, line.text).as_bytes())?;
+                if last == Some(i + j) {
+                    bytes_written += self.writer.write(NO_NEWLINE.as_bytes())?;
+                }
+            }
+            i = end;
+        }
+
+        Ok(bytes_written)
+    }
+}
+
+impl Presenter for ContextPresenter
+{
+    fn header(&mut self, _old : &str, _new : &str) -> std::result::Result<usize, std::io::Error>
+    {
+        self.writer.write(format!("*** {}\n--- {}\n", _old, _new).as_bytes())
+    }
+
+    fn present(&mut self, _old : &str, _new : &str, old_line : Option<u32>, new_line : Option<u32>, result : &[DiffCell]) -> std::result::Result<usize, std::io::Error>
+    {
+        self.recorder.record(_old, _new, old_line, new_line, result);
+        Ok(0)
+    }
+
+    fn present_moved(&mut self, line : &str, _line_num : u32, _other_line_num : u32, source : bool) -> std::result::Result<usize, std::io::Error>
+    {
+        self.recorder.record_moved(line, source);
+        Ok(0)
+    }
+
+    fn present_unchanged(&mut self, line : &str, _old_line : u32, _new_line : u32) -> std::result::Result<usize, std::io::Error>
+    {
+        self.recorder.record_unchanged(line);
+        Ok(0)
+    }
+
+    fn set_no_newline(&mut self, _old : bool, _new : bool)
+    {
+        self.recorder.set_no_newline(_old, _new);
+    }
+
+    fn present_message(&mut self, text : &str) -> std::result::Result<usize, std::io::Error>
+    {
+        self.writer.write(format!("{}\n", text).as_bytes())
+    }
+
+    fn footer(&mut self) -> std::result::Result<usize, std::io::Error>
+    {
+        let mut bytes_written = 0;
+        for hunk in self.recorder.take_hunks(self.context)
+        {
+            bytes_written += self.writer.write(format!("***************\n*** {} ****\n", range(hunk.old_start, hunk.old_count)).as_bytes())?;
+            if hunk.lines.iter().any(|l| l.kind == LineKind::Remove) {
+                bytes_written += self.write_section(&hunk, LineKind::Remove)?;
+            }
+
+            bytes_written += self.writer.write(format!("--- {} ----\n", range(hunk.new_start, hunk.new_count)).as_bytes())?;
+            if hunk.lines.iter().any(|l| l.kind == LineKind::Insert) {
+                bytes_written += self.write_section(&hunk, LineKind::Insert)?;
+            }
+        }
+        self.writer.flush()?;
+
+        Ok(bytes_written)
+    }
+}
+
+
+///Range of hunk section: 1 based first and last line, single number for single line,
+///empty range is the line before it
+fn range(start : usize, count : usize) -> String
+{
+    match count
+    {
+        0 => format!("{}", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, start + count),
+    }
+}
+
+
+#[cfg(test)]
+mod tests
+{
+    use std::rc::Rc;
+    use std::cell::RefCell;
+    use ::diff::context::*;
+    use ::diff::presenter::SharedBuffer;
+
+    #[test]
+    fn context_format()
+    {
+        let buffer = Rc::new(RefCell::new(Vec::new()));
+        let mut presenter = ContextPresenter::new(Box::new(SharedBuffer(buffer.clone())), 1);
+
+        presenter.header("a.txt", "b.txt").unwrap();
+        presenter.present_unchanged("one", 1, 1).unwrap();
+        presenter.present("two", "2", Some(2), Some(2), &[]).unwrap();
+        presenter.present("three", "", Some(3), None, &[]).unwrap();
+        presenter.present_unchanged("four", 4, 3).unwrap();
+        presenter.present_unchanged("five", 5, 4).unwrap();
+        presenter.present_unchanged("six", 6, 5).unwrap();
+        presenter.present("", "seven", None, Some(6), &[]).unwrap();
+        presenter.footer().unwrap();
+
+        let out = String::from_utf8(buffer.borrow().clone()).unwrap();
+        assert_eq!(out, "*** a.txt\n--- b.txt\n\
+                         ***************\n*** 1,4 ****\n  one\n! two\n! three\n  four\n--- 1,3 ----\n  one\n! 2\n  four\n\
+                         ***************\n*** 6 ****\n--- 5,6 ----\n  six\n+ seven\n");
+    }
+
+    #[test]
+    fn no_newline_markers()
+    {
+        let buffer = Rc::new(RefCell::new(Vec::new()));
+        let mut presenter = ContextPresenter::new(Box::new(SharedBuffer(buffer.clone())), 1);
+
+        presenter.set_no_newline(true, true);
+        presenter.header("a.txt", "b.txt").unwrap();
+        presenter.present_unchanged("one", 1, 1).unwrap();
+        presenter.present("two", "2", Some(2), Some(2), &[]).unwrap();
+        presenter.present_unchanged("three", 3, 3).unwrap();
+        presenter.footer().unwrap();
+
+        //marker follows the last line of each side, context line included
+        let out = String::from_utf8(buffer.borrow().clone()).unwrap();
+        assert_eq!(out, "*** a.txt\n--- b.txt\n\
+                         ***************\n*** 1,3 ****\n  one\n! two\n  three\n\\ No newline at end of file\n\
+                         --- 1,3 ----\n  one\n! 2\n  three\n\\ No newline at end of file\n");
+    }
+}
@@ -8,63 +8,122 @@
 //!   let r    = String::from("Hello wrld");
 //!   let sd   = StringDiff::new(); 
 //!   let diff = sd.get_diff(&l,&r);
+//!   let dist = sd.distance(&l,&r);
+//!   let near = sd.get_diff_bounded(&l,&r, 3);   //None when strings are too different
+//!   let ops  = sd.get_opcodes(&l,&r);           //Equal, Insert, Delete, Replace ranges from the beginning
+//!   let hunks = sd.get_grouped_opcodes(&l,&r, 3); //the same, grouped with 3 characters of context
 //!
+//!   sd.set_timeout(Some(Duration::from_millis(100)));   //valid, but maybe not minimal result afterwards
+//!   let best = sd.get_close_matches("appel", &["ape", "apple", "peach"], 3, 0.6);
 //!
+//!   let costs = DiffCosts { update : 2, transpose : Some(1), .. DiffCosts::default() };
+//!   let wsd   = StringDiff::with_costs(costs);
 //!
-use diff::math::Matrix;
-use std::cmp::min;
+//!   //comparing many strings, result vector and matrices are reused
+//!   let mut cells = Vec::new();
+//!   for (l, r) in pairs {
+//!       sd.get_diff_into(l, r, &mut cells);
+//!   }
+//!
+//!
+//!
+use diff::math::{Matrix,PackedMatrix};
+use diff::bitparallel;
+use std::cmp::{max,min,Ordering};
+use std::mem::{self,size_of};
+use std::ops::Range;
+use std::time::{Duration,Instant};
 use std::fmt::Formatter;
 use std::fmt::Result;
 use std::fmt::Display;
 
 
-//helper method
-#[cfg(debug_assertions)]
-#[allow(dead_code)]
-fn print_matrix<T>(mtx : &Matrix<T>)
-    where T : Clone + Default + Display
-{
-    for row in 0..mtx.r_cnt
-    {
-        for col in 0..mtx.c_cnt
-        {
-            print!("{}-", mtx[(row, col)]);
-        }
-
-        println!("");
-    }
-}
-
 /// Cell that discribes single occuried difference between strings.
 /// start: Start index of character in the left string provide 
+/// new_start: Start index of the same position in the right string, 
+///            so inserted characters are taken from there without replaying preceding cells
 /// count: Count of consecutive characters affected by the _same_ difference 
+///        (for Transpose every pair of characters in the range is swapped)
 /// operation: Difference operation  
 #[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct DiffCell
 {
     pub start : usize,
+    pub new_start : usize,
     pub count : usize,
     pub operation : DiffOperation
 }
 
+impl DiffCell
+{
+    /// Characters of the left string affected by the cell, empty for insertion
+    pub fn old_range(&self) -> Range<usize>
+    {
+        match self.operation
+        {
+            DiffOperation::Insert => self.start .. self.start,
+            _ => self.start .. self.start + self.count,
+        }
+    }
+
+    /// Characters of the right string affected by the cell, empty for removal
+    pub fn new_range(&self) -> Range<usize>
+    {
+        match self.operation
+        {
+            DiffOperation::Remove => self.new_start .. self.new_start,
+            _ => self.new_start .. self.new_start + self.count,
+        }
+    }
+}
+
+
+/// Differences replacing whole `_old` string with `_new`, in the form of StringDiff::get_diff:
+/// tail of the longer string is inserted or removed, the rest is updated
+pub fn full_replace(_old : &str, _new : &str) -> Vec<DiffCell>
+{
+    let (old_len, new_len) = (_old.chars().count(), _new.chars().count());
+    let len = min(old_len, new_len);
 
-/// Types of operation required to execute on current character, or range of characters 
+    let mut v = Vec::new();
+    if old_len > len {
+        v.push(DiffCell { start : len, new_start : len, count : old_len - len, operation : DiffOperation::Remove });
+    }
+    else if new_len > len {
+        v.push(DiffCell { start : len, new_start : len, count : new_len - len, operation : DiffOperation::Insert });
+    }
+    if len > 0 {
+        v.push(DiffCell { start : 0, new_start : 0, count : len, operation : DiffOperation::Update });
+    }
+
+    v
+}
+
+
+/// Types of operation required to execute on current character, or range of characters.
+/// With serde feature unit variants are serialized as snake case strings ("insert"),
+/// Moved as an object: {"moved": {"from": 1, "to": 5}} 
 #[derive(Debug)]
 #[derive(PartialEq)]
 #[derive(Clone)]
+#[derive(Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum DiffOperation 
 {
     Insert,
     Remove,    
     Update,
+    Transpose,
+    /// Block moved from index `from` of old sequence to index `to` of new sequence 
+    Moved { from : usize, to : usize },
+    #[default]
     None
 }
 
-impl Default for DiffOperation
-{
-    fn default() -> DiffOperation {DiffOperation::None}
-}
-
 impl Display for DiffOperation
 {
     fn fmt(&self, f: &mut Formatter) -> Result {
@@ -72,6 +131,8 @@ impl Display for DiffOperation
             DiffOperation::Insert =>  write!(f, "(i)"),
             DiffOperation::Remove =>  write!(f, "(r)"),
             DiffOperation::Update =>  write!(f, "(u)"),
+            DiffOperation::Transpose =>  write!(f, "(t)"),
+            DiffOperation::Moved{..} =>  write!(f, "(m)"),
             _      =>  write!(f, "(-)"),
         }
        
@@ -79,150 +140,765 @@ impl Display for DiffOperation
 }
 
 
+/// Kind of opcode: how the range of old string turns into the range of new one
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum OpcodeTag
+{
+    Equal,
+    Insert,
+    Delete,
+    Replace,
+}
+
+
+/// Step of alignment of 2 strings, as returned by StringDiff::get_opcodes.
+/// old, new: character ranges of old and new string, empty for inserted and deleted characters.
+/// Opcodes are in forward order and every one starts where the previous one ends
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Opcode
+{
+    pub tag : OpcodeTag,
+    pub old : Range<usize>,
+    pub new : Range<usize>,
+}
+
+
+/// Opcodes of alignment given by positions of equal characters in both strings, in forward order.
+/// Characters between 2 runs of equal ones form a single opcode
+fn opcodes_of(matches : &[(usize, usize)], old_len : usize, new_len : usize) -> Vec<Opcode>
+{
+    let mut ops = Vec::new();
+    let (mut i, mut j, mut k) = (0, 0, 0);
+    loop
+    {
+        //changed characters up to the next equal pair, or to the end of both strings
+        let (next_i, next_j) = matches.get(k).cloned().unwrap_or((old_len, new_len));
+        let tag = match (i < next_i, j < next_j)
+        {
+            (true, true)  => Some(OpcodeTag::Replace),
+            (true, false) => Some(OpcodeTag::Delete),
+            (false, true) => Some(OpcodeTag::Insert),
+            _ => None,
+        };
+        if let Some(tag) = tag {
+            ops.push(Opcode { tag, old : i .. next_i, new : j .. next_j });
+        }
+        if k == matches.len() {
+            break;
+        }
+
+        //run of equal pairs on the same diagonal
+        let mut end = k + 1;
+        while end < matches.len() && matches[end] == (matches[end - 1].0 + 1, matches[end - 1].1 + 1) {
+            end += 1;
+        }
+        i = next_i + end - k;
+        j = next_j + end - k;
+        ops.push(Opcode { tag : OpcodeTag::Equal, old : next_i .. i, new : next_j .. j });
+        k = end;
+    }
+
+    ops
+}
+
+
+/// Groups opcodes into hunks: changes with up to `context` equal elements around them,
+/// like SequenceMatcher.get_grouped_opcodes of Python. Changes separated by no more than
+/// 2 * context equal elements share one hunk, equal opcodes at the edges of hunks are shortened.
+/// Works for any sequence the opcodes describe: characters of strings, lines of files.
+pub fn group_opcodes(ops : &[Opcode], context : usize) -> Vec<Vec<Opcode>>
+{
+    let mut groups = Vec::new();
+    let mut group : Vec<Opcode> = Vec::new();
+    for (k, op) in ops.iter().enumerate()
+    {
+        if op.tag != OpcodeTag::Equal 
+        {
+            group.push(op.clone());
+            continue;
+        }
+
+        //context after the previous change and before the next one
+        let len  = op.old.len();
+        let head = if k == 0 { 0 } else { min(len, context) };
+        let tail = if k + 1 == ops.len() { 0 } else { min(len, context) };
+        if head + tail >= len 
+        {
+            group.push(op.clone());
+            continue;
+        }
+
+        if head > 0 {
+            group.push(Opcode { tag : OpcodeTag::Equal, old : op.old.start .. op.old.start + head, new : op.new.start .. op.new.start + head });
+        }
+        if !group.is_empty() {
+            groups.push(mem::take(&mut group));
+        }
+        if tail > 0 {
+            group.push(Opcode { tag : OpcodeTag::Equal, old : op.old.end - tail .. op.old.end, new : op.new.end - tail .. op.new.end });
+        }
+    }
+
+    if group.iter().any(|op| op.tag != OpcodeTag::Equal) {
+        groups.push(group);
+    }
+    groups
+}
+
+
+/// Costs of operations used to compute edit distance.
+/// insert, remove, update: Cost of inserting, removing and updating single character 
+/// transpose: Cost of swapping 2 adjacent characters, None disables transposition detection 
+/// insert_cost, remove_cost, update_cost: Optional per character costs, used instead of constant ones, 
+///                                        e.g. to make particular character classes cheaper 
+#[derive(Clone)]
+pub struct DiffCosts
+{
+    pub insert : u32,
+    pub remove : u32,
+    pub update : u32,
+    pub transpose : Option<u32>,
+    pub insert_cost : Option<fn(char) -> u32>,
+    pub remove_cost : Option<fn(char) -> u32>,
+    pub update_cost : Option<fn(char, char) -> u32>
+}
+
+impl Default for DiffCosts
+{
+    fn default() -> DiffCosts
+    {
+        DiffCosts { insert : 1, remove : 1, update : 1, transpose : None, 
+                    insert_cost : None, remove_cost : None, update_cost : None }
+    }
+}
+
+impl DiffCosts
+{
+    ///Cost of inserting character
+    #[inline]
+    pub fn insert_of(&self, ch : char) -> u32
+    {
+        self.insert_cost.map_or(self.insert, |f| f(ch))
+    }
+
+    ///Cost of removing character
+    #[inline]
+    pub fn remove_of(&self, ch : char) -> u32
+    {
+        self.remove_cost.map_or(self.remove, |f| f(ch))
+    }
+
+    ///Cost of replacing `from` character with `to` 
+    #[inline]
+    pub fn update_of(&self, from : char, to : char) -> u32
+    {
+        self.update_cost.map_or(self.update, |f| f(from, to))
+    }
+
+    ///True for costs of Levenshtein distance: every operation costs 1, no transposition
+    pub fn is_unit(&self) -> bool
+    {
+        self.insert == 1 && self.remove == 1 && self.update == 1 && self.transpose.is_none()
+            && self.insert_cost.is_none() && self.remove_cost.is_none() && self.update_cost.is_none()
+    }
+}
+
+
+/// Size of matrices of new StringDiff, strings up to this length are compared without allocation
+const INITIAL_SIZE : usize = 512;
+
+/// Cost of matrix cells outside of the computed band, low enough to add costs to it without overflow
+const UNREACHABLE : u32 = u32::MAX / 2;
+
+/// Default memory limit of matrices: 64 MiB, about 16000 x 16000 characters
+pub const DEFAULT_MEMORY_LIMIT : usize = 64 * 1024 * 1024;
+
+
 pub struct StringDiff
 {
-    mtx : Matrix<u32>,
-    directions : Matrix<DiffOperation>
+    mtx : Matrix<u32>,               //rolling rows of costs, row r is kept in row r % rows
+    directions : PackedMatrix,       //traceback, 2 bits per cell, see direction_code
+    costs : DiffCosts,
+    memory_limit : usize,
+    timeout : Option<Duration>,
+    deadline : Option<Instant>,
+    call_deadline : Option<Instant>, //the earlier of both, for the running comparison
+    timed_out : bool
 }   
 
 
+///Outcome of matrix computation
+#[derive(PartialEq)]
+enum MatrixState
+{
+    Complete,
+    OverBound,
+    OutOfTime,
+}
+
+
 impl StringDiff
 {
     pub fn new() -> StringDiff
     {           
-       let matrix : Matrix<u32>  = Matrix::new(512, 512);     
-       let dirs : Matrix<DiffOperation> = Matrix::new(512, 512);
-       StringDiff { mtx : matrix, directions: dirs}
+       StringDiff::with_costs(DiffCosts::default())
+    }
+
+    /// Creates diff which uses provided operation costs 
+    pub fn with_costs(costs : DiffCosts) -> StringDiff
+    {           
+       let matrix : Matrix<u32>  = Matrix::new(3, INITIAL_SIZE);     
+       let dirs = PackedMatrix::new(INITIAL_SIZE, INITIAL_SIZE);
+       StringDiff { mtx : matrix, directions: dirs, costs, memory_limit : DEFAULT_MEMORY_LIMIT, 
+                    timeout : None, deadline : None, call_deadline : None, timed_out : false }
+    }
+
+    /// Replaces operation costs used by subsequent calls 
+    #[allow(dead_code)]
+    pub fn set_costs(&mut self, costs : DiffCosts)
+    {
+        self.costs = costs;
+    }
+
+
+    /// Sets time single comparison may take, None by default. Comparison which runs out of time
+    /// returns valid, but possibly not minimal differences: the part of strings, which was not 
+    /// compared yet, is reported as updated whole. Distance is an upper bound then
+    #[allow(dead_code)]
+    pub fn set_timeout(&mut self, timeout : Option<Duration>)
+    {
+        self.timeout = timeout;
+    }
+
+    /// Sets time, after which comparisons end the same way as on timeout, e.g. to limit
+    /// the time of all comparisons of a request. None by default
+    pub fn set_deadline(&mut self, deadline : Option<Instant>)
+    {
+        self.deadline = deadline;
+    }
+
+    /// True when the last comparison ran out of time, its result may be not minimal
+    #[allow(dead_code)]
+    pub fn timed_out(&self) -> bool
+    {
+        self.timed_out
+    }
+
+
+    /// Sets size of matrices in bytes, after which they are released at the end of comparison.
+    /// Matrices keep growing to the biggest compared strings otherwise
+    #[allow(dead_code)]
+    pub fn set_memory_limit(&mut self, bytes : usize)
+    {
+        self.memory_limit = bytes;
+    }
+
+    /// Bytes currently allocated by matrices
+    pub fn memory_usage(&self) -> usize
+    {
+        self.mtx.capacity() * size_of::<u32>() + self.directions.capacity()
+    }
+
+    /// Releases matrices, the next comparison allocates them again
+    pub fn release_buffers(&mut self)
+    {
+        self.mtx.clear();
+        self.directions.clear();
     }
 
 
     /// Returns a vector of differences occured in 2 strings provided as parameters
     pub fn get_diff(&mut self, _old : &str, _new : &str) -> Vec<DiffCell>
     {            
+        let mut v = Vec::new();
+        self.get_diff_into(_old, _new, &mut v);
+        v
+    }
+
+
+    /// Same as get_diff, but writes differences to provided vector, which is cleared first.
+    /// Reusing the vector avoids allocation on every call, when many strings are compared
+    pub fn get_diff_into(&mut self, _old : &str, _new : &str, v : &mut Vec<DiffCell>)
+    {
+        self.diff_into(_old, _new, None, v);
+    }
+
+
+    /// Same as get_diff, when total cost of differences is at most `max_d`, None otherwise: strings are too different.
+    /// Only the diagonal band of the matrix, which paths within `max_d` can reach, is computed
+    /// and the computation stops, as soon as every path exceeds the bound
+    #[allow(dead_code)]
+    pub fn get_diff_bounded(&mut self, _old : &str, _new : &str, max_d : u32) -> Option<Vec<DiffCell>>
+    {
+        let mut v = Vec::new();
+        if self.get_diff_bounded_into(_old, _new, max_d, &mut v) { Some(v) } else { None }
+    }
+
+
+    /// Same as get_diff_bounded, but writes differences to provided vector. 
+    /// Returns false, when strings are too different, the vector is empty then
+    pub fn get_diff_bounded_into(&mut self, _old : &str, _new : &str, max_d : u32, v : &mut Vec<DiffCell>) -> bool
+    {
+        let within = self.diff_into(_old, _new, Some(max_d), v);
+        if !within {
+            v.clear();
+        }
+        within
+    }
+
+
+    /// Returns alignment of 2 strings in forward order: equal, inserted, deleted and replaced
+    /// character ranges, which cover both strings completely, like SequenceMatcher.get_opcodes
+    /// of Python. Transposed characters are reported as replaced. Unlike get_diff, strings 
+    /// are not trimmed to equal length. On timeout the part not compared yet is replaced whole
+    #[allow(dead_code)]
+    pub fn get_opcodes(&mut self, _old : &str, _new : &str) -> Vec<Opcode>
+    {
+        self.start_call();
+        let old_chars : Vec<char> = _old.chars().collect();
+        let new_chars : Vec<char> = _new.chars().collect();
+
+        //equal characters of the alignment, shared prefix and suffix are always part of it
+        let (prefix, suffix) = self.common_affixes(&old_chars, &new_chars);
+        let mut matches : Vec<(usize, usize)> = (0 .. prefix).map(|i| (i, i)).collect();
+
+        let old_middle = &old_chars[prefix .. old_chars.len() - suffix];
+        let new_middle = &new_chars[prefix .. new_chars.len() - suffix];
+        self.prepare_matrix(old_middle.len() + 1, new_middle.len() + 1);
+        if self.compile_matrix(old_middle, new_middle, None) == MatrixState::Complete
+        {
+            self.collect_matches(old_middle, new_middle, prefix, &mut matches);
+            matches[prefix ..].reverse();
+        }
+        matches.extend((1 ..= suffix).rev().map(|k| (old_chars.len() - k, new_chars.len() - k)));
+        self.limit_memory();
 
+        opcodes_of(&matches, old_chars.len(), new_chars.len())
+    }
+
+
+    /// Returns opcodes of 2 strings grouped into hunks with `context` equal characters around changes,
+    /// see group_opcodes. Equal strings have no hunks
+    #[allow(dead_code)]
+    pub fn get_grouped_opcodes(&mut self, _old : &str, _new : &str, context : usize) -> Vec<Vec<Opcode>>
+    {
+        group_opcodes(&self.get_opcodes(_old, _new), context)
+    }
+
+
+    ///Computes differences, with `max_d` bound of their total cost if any. Returns false when it is exceeded 
+    fn diff_into(&mut self, _old : &str, _new : &str, max_d : Option<u32>, v : &mut Vec<DiffCell>) -> bool
+    {
+        v.clear();
+        self.start_call();
         if _old == _new 
         {
-            return vec![];
+            return true;
         }
 
         let mut row_count       =  _old.chars().count() + 1;  //space for 0s
         let mut column_count    =  _new.chars().count() + 1; //space for 0s
         
-        //trim strings to equal size
-        let mut v   = Vec::with_capacity(512);
+        //trim strings to equal size, cost of the trimmed tail is taken from the bound
+        let mut tail_cost = 0;
         if row_count > column_count {
-            v.push(DiffCell{start: column_count - 1, count: row_count - column_count, operation: DiffOperation::Remove});
+            v.push(DiffCell{start: column_count - 1, new_start: column_count - 1, count: row_count - column_count, operation: DiffOperation::Remove});
+            tail_cost = _old.chars().skip(column_count - 1).map(|ch| self.costs.remove_of(ch)).sum();
             row_count = column_count;
         }
         else if column_count > row_count {
-            v.push(DiffCell{start: row_count - 1, count: column_count - row_count, operation: DiffOperation::Insert});
+            v.push(DiffCell{start: row_count - 1, new_start: row_count - 1, count: column_count - row_count, operation: DiffOperation::Insert});
+            tail_cost = _new.chars().skip(row_count - 1).map(|ch| self.costs.insert_of(ch)).sum();
             column_count = row_count; 
         }
 
+        let max_d = match max_d {
+            Some(d) if d < tail_cost => return false,
+            Some(d) => Some(d - tail_cost),
+            None => None,
+        };
        
         if row_count == 1 && column_count > 1
         {
-            return vec![DiffCell{start: 0, count: column_count, operation: DiffOperation::Insert}];  
+            *v = vec![DiffCell{start: 0, new_start: 0, count: column_count, operation: DiffOperation::Insert}];  
+            return true;
         }
         else if row_count > 1 && column_count == 1
         {
-            return vec![DiffCell{start: 0, count: column_count, operation: DiffOperation::Remove}]; 
+            *v = vec![DiffCell{start: 0, new_start: 0, count: column_count, operation: DiffOperation::Remove}]; 
+            return true;
         }
        
         
-        //init bigger matrix
-        if row_count > self.mtx.r_tot_cnt || column_count > self.mtx.c_tot_cnt
-        {               
-            self.mtx        =  Matrix::new(row_count, column_count);
-            self.directions =  Matrix::new(row_count, column_count);
+        let old_chars : Vec<char> = _old.chars().take(row_count - 1).collect();
+        let new_chars : Vec<char> = _new.chars().take(column_count - 1).collect();
+
+        //only the middle between shared prefix and suffix is compared 
+        let (prefix, suffix) = self.common_affixes(&old_chars, &new_chars);
+        let tail_len = v.len();
+        let mut result = self.diff_middle(&old_chars, &new_chars, prefix, suffix, max_d, v);
+        if result == Some(false)
+        {
+            //path of the whole strings would continue through the prefix, compare it too
+            v.truncate(tail_len);
+            result = self.diff_middle(&old_chars, &new_chars, 0, suffix, max_d, v);
         }
-        else {
-            //set row and column count to the current, requred size of source strings
-            self.mtx.set_size(row_count, column_count);
-            self.directions.set_size(row_count, column_count);
-           
+        self.limit_memory();
+
+        result.is_some()
+    }
+
+
+    /// Returns edit distance between 2 strings provided as parameters:
+    /// minimal total cost of inserted, removed, updated (and transposed, if enabled) characters 
+    pub fn distance(&mut self, _old : &str, _new : &str) -> u32
+    {
+        self.start_call();
+        if _old == _new 
+        {
+            return 0;
         }
-       
-      
-        self.compile_matrix(&_old, &_new);           
-        self.fill_result(&mut v);        
 
-        v
+        let old_chars : Vec<char> = _old.chars().collect();
+        let new_chars : Vec<char> = _new.chars().collect();
+
+        //prefix never changes the distance, when it can be stripped 
+        let (prefix, suffix) = self.common_affixes(&old_chars, &new_chars);
+        let old_middle = &old_chars[prefix .. old_chars.len() - suffix];
+        let new_middle = &new_chars[prefix .. new_chars.len() - suffix];
+
+        //short strings with unit costs are compared by bit vectors, without matrix
+        if self.costs.is_unit()
+        {
+            if let Some(distance) = bitparallel::distance(old_middle, new_middle) {
+                return distance;
+            }
+        }
+
+        self.prepare_matrix(old_middle.len() + 1, new_middle.len() + 1);
+        let distance = match self.compile_matrix(old_middle, new_middle, None) 
+        {
+            MatrixState::OutOfTime => self.replace_cost(old_middle, new_middle),
+            _ => self.last_cost(old_middle.len()),
+        };
+        self.limit_memory();
+        distance
+    }
+
+
+    /// Returns similarity of 2 strings in range [0, 1], where 1 means equal strings. 
+    /// Computed as 1 - distance / length of the longer string, clamped to 0 for costs above 1.
+    pub fn ratio(&mut self, _old : &str, _new : &str) -> f64
+    {
+        let longest = max(_old.chars().count(), _new.chars().count());
+        if longest == 0 
+        {
+            return 1.0;
+        }
+
+        (1.0 - self.distance(_old, _new) as f64 / longest as f64).max(0.0)
+    }
+
+
+    /// Returns up to `n` candidates which ratio to `word` is at least `cutoff`,
+    /// the most similar first. Candidates with equal ratio keep their original order.
+    #[allow(dead_code)]
+    pub fn get_close_matches<'a>(&mut self, word : &str, candidates : &[&'a str], n : usize, cutoff : f64) -> Vec<&'a str>
+    {
+        let mut scored : Vec<(f64, &'a str)> = candidates.iter()
+                                                   .map(|c| (self.ratio(word, c), *c))
+                                                   .filter(|&(score, _)| score >= cutoff)
+                                                   .collect();
+
+        scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+        scored.into_iter().take(n).map(|(_, c)| c).collect()
+    }
+
+
+    ///Makes matrices big enough to compare strings of given sizes 
+    fn prepare_matrix(&mut self, row_count : usize, column_count : usize)
+    {
+        //allocation is only grown, init_matrix and compile_matrix overwrite cells they read.
+        //Costs of a row depend on the previous one, transposition needs the one before it too
+        let cost_rows = if self.costs.transpose.is_some() { 3 } else { 2 };
+        self.mtx.reshape(cost_rows, column_count);
+        self.directions.reshape(row_count, column_count);
+    }
+
+
+    ///Lengths of shared prefix and suffix (not overlapping the prefix) of compared characters. 
+    ///Equal characters there cost nothing, when every character costs the same to insert
+    ///and to remove, so only the middle is compared. Nothing is shared with per character 
+    ///insert or remove costs, prefix is not shared with transposition, which may swap 
+    ///the first character of the middle with the last one of the prefix.
+    fn common_affixes(&self, old_chars : &[char], new_chars : &[char]) -> (usize, usize)
+    {
+        if self.costs.insert_cost.is_some() || self.costs.remove_cost.is_some() {
+            return (0, 0);
+        }
+
+        let prefix = match self.costs.transpose 
+        {
+            Some(_) => 0,
+            None    => old_chars.iter().zip(new_chars).take_while(|(o, n)| o == n).count(),
+        };
+        let suffix = old_chars[prefix ..].iter().rev()
+                        .zip(new_chars[prefix ..].iter().rev())
+                        .take_while(|(o, n)| o == n)
+                        .count();
+
+        (prefix, suffix)
+    }
+
+
+    ///Compares characters between `prefix` and `suffix` and appends their differences.
+    ///Returns None, when their cost exceeds `max_d`. Returns false, when the path of differences 
+    ///does not end at the beginning of the middle: on equal costs it would go on through the prefix,
+    ///so the result may differ from the one of whole strings.
+    fn diff_middle(&mut self, old_chars : &[char], new_chars : &[char], prefix : usize, suffix : usize, max_d : Option<u32>, v : &mut Vec<DiffCell>) -> Option<bool>
+    {
+        let old_middle = &old_chars[prefix .. old_chars.len() - suffix];
+        let new_middle = &new_chars[prefix .. new_chars.len() - suffix];
+
+        self.prepare_matrix(old_middle.len() + 1, new_middle.len() + 1);
+        match self.compile_matrix(old_middle, new_middle, max_d)
+        {
+            MatrixState::OverBound => None,
+            MatrixState::OutOfTime => {
+                //strings are trimmed to equal length, so is the middle
+                if !old_middle.is_empty() {
+                    v.push(DiffCell { start : prefix, new_start : prefix, count : old_middle.len(), operation : DiffOperation::Update });
+                }
+                Some(true)
+            },
+            MatrixState::Complete => {
+                let end = self.fill_result(old_middle, new_middle, v, prefix);
+                Some(prefix == 0 || end == (0, 0))
+            },
+        }
+    }
+
+
+    ///Starts comparison: its deadline is the earlier of the deadline and the end of the timeout
+    fn start_call(&mut self)
+    {
+        self.timed_out = false;
+        self.call_deadline = match (self.deadline, self.timeout.map(|t| Instant::now() + t))
+        {
+            (Some(d), Some(t)) => Some(min(d, t)),
+            (d, t) => d.or(t),
+        };
     }
 
 
-    ///Resets matrix to its original state, filled with 0s
-    fn init_matrix(&mut self)
+    ///Cost of replacing characters at the same positions and inserting or removing the rest,
+    ///upper bound of the distance
+    fn replace_cost(&self, old_chars : &[char], new_chars : &[char]) -> u32
+    {
+        let replaced : u32 = old_chars.iter().zip(new_chars)
+            .map(|(&o, &n)| if o == n { 0 } else { min(self.costs.update_of(o, n), self.costs.remove_of(o) + self.costs.insert_of(n)) })
+            .sum();
+        let removed  : u32 = old_chars.iter().skip(new_chars.len()).map(|&o| self.costs.remove_of(o)).sum();
+        let inserted : u32 = new_chars.iter().skip(old_chars.len()).map(|&n| self.costs.insert_of(n)).sum();
+
+        replaced + removed + inserted
+    }
+
+
+    ///Releases matrices grown over the memory limit
+    fn limit_memory(&mut self)
+    {
+        if self.memory_usage() > self.memory_limit {
+            self.release_buffers();
+        }
+    }
+
+
+    ///Resets matrix to its original state: first row is filled with the cost of inserting
+    ///all preceding characters. The first column, cost of removing them, is filled by compile_matrix
+    ///with the row it belongs to. The rest of the matrix is overwritten by compile_matrix, it is not cleared
+    fn init_matrix(&mut self, new_chars : &[char])
     {    
-        self.mtx.fill_with_val(0); 
+        self.mtx[(0, 0)] = 0;
 
         //first row  0..col_count
-        let col_count = self.mtx.c_cnt;
-        for c in  0 .. col_count
+        let col_count = self.mtx.cols();
+        for c in  1 .. col_count
         {
-           self.mtx[(0, c)] = c as u32;  
+           self.mtx[(0, c)] = self.mtx[(0, c - 1)] + self.costs.insert_of(new_chars[c - 1]);  
         }  
+    }
 
-        //first column 0.. row_count
-        let row_count = self.mtx.r_cnt;        
-        for r in  0 .. row_count 
-        {
-           self.mtx[(r, 0)] = r as u32;  
-        }
 
+    ///Cost in the last column of computed `row`
+    fn last_cost(&self, row : usize) -> u32
+    {
+        self.mtx[(row % self.mtx.rows(), self.mtx.cols() - 1)]
     }
 
-    ///Get concrete direction from the cell of the Matrix
-    fn get_curdir(&self, i : usize, j : usize) -> DiffOperation
-    {              
-        if self.mtx[(i+1, j+1)] == self.mtx[(i, j)] + 1 {
-            DiffOperation::Update //update                        
+
+    ///Code of the traceback direction stored in 2 bits. None and transposition share the code:
+    ///None is only chosen for equal characters, transposition only for different ones
+    fn direction_code(op : &DiffOperation) -> u8
+    {
+        match op
+        {
+            DiffOperation::Update => 1,
+            DiffOperation::Remove => 2,
+            DiffOperation::Insert => 3,
+            _ => 0,
         }
-        else if self.mtx[(i+1, j+1)] == self.mtx[(i, j+1)] + 1 {                     
-            DiffOperation::Remove //remove                
+    }
+
+
+    ///Traceback direction of the cell, characters of the cell tell None from transposition
+    fn direction(&self, row : usize, col : usize, old_chars : &[char], new_chars : &[char]) -> DiffOperation
+    {
+        match self.directions.get(row, col)
+        {
+            1 => DiffOperation::Update,
+            2 => DiffOperation::Remove,
+            3 => DiffOperation::Insert,
+            _ if old_chars[row - 1] == new_chars[col - 1] => DiffOperation::None,
+            _ => DiffOperation::Transpose,
         }
-        else if self.mtx[(i+1, j+1)] == self.mtx[(i+1, j)] + 1 {                         
-             DiffOperation::Insert //insert                
-        }   
-        else {
-            DiffOperation::None
-        }              
-              
-    }    
+    }
 
-    
     /// Naive implementation of Eugene W. Myer's string diff algorithm 
     /// Paper source: http://www.xmailserver.org/diff2.pdf
-    fn compile_matrix(&mut self, _old : &str, _new : &str) 
+    /// When transposition is enabled, adjacent swapped characters are 
+    /// detected as in optimal string alignment (restricted Damerau-Levenshtein) distance.
+    /// With `max_d` only cells of the diagonal band reachable within it are computed, cells around 
+    /// the band are marked unreachable. Stops, when every path of a row exceeds `max_d`,
+    /// or when the deadline of comparison passes.
+    /// Only last rows of costs are kept, directions of all cells are kept for fill_result
+    fn compile_matrix(&mut self, old_chars : &[char], new_chars : &[char], max_d : Option<u32>) -> MatrixState
     {          
-        self.init_matrix();
+        self.init_matrix(new_chars);
+        let cost_rows = self.mtx.rows();
+
+        //each step off the diagonal inserts or removes character. Per character costs
+        //may be 0 for some characters, paths within max_d can go anywhere then
+        let band = match max_d 
+        {
+            Some(d) if self.costs.insert_cost.is_none() && self.costs.remove_cost.is_none() => {
+                d.checked_div(min(self.costs.insert, self.costs.remove)).map_or(usize::MAX, |b| b as usize)
+            },
+            _ => usize::MAX,
+        };
         
-        for (i, o_ch) in _old.chars().enumerate().take(self.mtx.r_cnt-1) 
+        for (i, &o_ch) in old_chars.iter().enumerate() 
         {               
-            for (j, n_ch) in _new.chars().enumerate().take(self.mtx.c_cnt-1) 
+            //rows of costs: before the previous one, previous one and the computed one
+            let (before, prev, cur) = ((i + cost_rows - 1) % cost_rows, i % cost_rows, (i + 1) % cost_rows);
+            self.mtx[(cur, 0)] = self.mtx[(prev, 0)] + self.costs.remove_of(o_ch);
+
+            //band of this row: cells j+1 for j in first .. last
+            let first = i.saturating_sub(band);
+            let end   = i.saturating_add(band).saturating_add(1);
+            let last  = min(new_chars.len(), end);
+            if first > 0 {
+                self.mtx[(cur, first)] = UNREACHABLE;
+            }
+            if i > 0 && end <= new_chars.len() {
+                self.mtx[(prev, end)] = UNREACHABLE;
+            }
+
+            let mut row_min = if first == 0 { self.mtx[(cur, 0)] } else { UNREACHABLE };
+            for (j, &n_ch) in new_chars.iter().enumerate().take(last).skip(first) 
             {     
-                if o_ch == n_ch {           
-                    //No change            
-                    self.mtx[(i+1, j+1)] =  self.mtx[(i, j)];        
-                    self.directions[(i+1, j+1)]= DiffOperation::None;                                                                                          
+                //candidates, on equal cost the first one wins
+                let mut best = (u32::MAX, DiffOperation::None);
+                let update   = self.costs.update_of(o_ch, n_ch);
+                if o_ch == n_ch {
+                    best = (self.mtx[(prev, j)], DiffOperation::None);
+                }
+                else if update < self.costs.remove_of(o_ch) + self.costs.insert_of(n_ch) {
+                    //update which costs as much as remove plus insert is reported as those two
+                    best = (self.mtx[(prev, j)] + update, DiffOperation::Update);
                 }
-                else {                    
-                    //Change 
-                    self.mtx[(i+1, j+1)] =  min(self.mtx[(i, j)], min(self.mtx[(i, j+1)], self.mtx[(i+1, j)])) + 1;                
-                    self.directions[(i+1, j+1)] =  self.get_curdir(i,j);  
+
+                if let Some(cost) = self.costs.transpose 
+                {
+                    if i > 0 && j > 0 && o_ch != n_ch && o_ch == new_chars[j - 1] && old_chars[i - 1] == n_ch 
+                            && self.mtx[(before, j - 1)] + cost < best.0
+                    {
+                        best = (self.mtx[(before, j - 1)] + cost, DiffOperation::Transpose);
+                    }
+                }
+
+                let remove = self.mtx[(prev, j+1)] + self.costs.remove_of(o_ch);
+                if remove < best.0 {
+                    best = (remove, DiffOperation::Remove);
                 }
+
+                let insert = self.mtx[(cur, j)] + self.costs.insert_of(n_ch);
+                if insert < best.0 {
+                    best = (insert, DiffOperation::Insert);
+                }
+
+                self.mtx[(cur, j+1)] = best.0;
+                self.directions.set(i+1, j+1, StringDiff::direction_code(&best.1));
+                row_min = min(row_min, best.0);
+            }
+
+            //costs only grow along a path
+            if max_d.is_some_and(|d| row_min > d) {
+                return MatrixState::OverBound;
+            }
+            if self.call_deadline.is_some_and(|d| Instant::now() >= d) {
+                self.timed_out = true;
+                return MatrixState::OutOfTime;
+            }
+        }
+
+        if max_d.is_some_and(|d| self.last_cost(old_chars.len()) > d) {
+            return MatrixState::OverBound;
+        }
+        MatrixState::Complete
+    }
+
+
+    /// Walks the whole path of differences from the end of the matrix to its beginning and appends
+    /// positions of equal characters, from the last one. `offset` is added to the positions
+    fn collect_matches(&self, old_chars : &[char], new_chars : &[char], offset : usize, matches : &mut Vec<(usize, usize)>)
+    {
+        let (mut row, mut col) = (old_chars.len(), new_chars.len());
+
+        //the rest of the first row or column is inserted or removed
+        while row > 0 && col > 0
+        {
+            match self.direction(row, col, old_chars, new_chars)
+            {
+                DiffOperation::None => {
+                    row -= 1;
+                    col -= 1;
+                    matches.push((offset + row, offset + col));
+                },
+                DiffOperation::Update    => { row -= 1; col -= 1; },
+                DiffOperation::Transpose => { row -= 2; col -= 2; },
+                DiffOperation::Remove    => row -= 1,
+                _ => col -= 1,
             }
         }
-        
     }
 
   
-    /// Compiles vector of differences computed from the matrix
-    fn fill_result(&self, v : &mut Vec<DiffCell>)
+    /// Compiles vector of differences computed from the matrix of compared characters,
+    /// `offset` is added to starts of cells: count of characters before compared ones.
+    /// Returns row and column where the path of differences ends
+    fn fill_result(&self, old_chars : &[char], new_chars : &[char], v : &mut Vec<DiffCell>, offset : usize) -> (usize, usize)
     {      
         
-        let mut row = self.mtx.r_cnt-1;
-        let mut col = self.mtx.c_cnt-1;
+        let mut row = old_chars.len();
+        let mut col = new_chars.len();
 
         
         while row > 0 && col > 0
@@ -232,57 +908,75 @@ impl StringDiff
                     
 
             while  row > 0 && col > 0 && 
-                        self.directions[(row, col)] == DiffOperation::None  //no change
+                        self.direction(row, col, old_chars, new_chars) == DiffOperation::None  //no change
             {
                 row -=1;
                 col -=1;         
             }           
 
             while row > 0 && col > 0 
-                    && self.directions[(row, col)] == DiffOperation::Update //update
+                    && self.direction(row, col, old_chars, new_chars) == DiffOperation::Update //update
             {                       
-                row = row - 1;
-                col = col - 1;     
-                op_count = op_count+1;        
+                row -= 1;
+                col -= 1;     
+                op_count += 1;        
                 op = DiffOperation::Update;             
             }
 
             if op != DiffOperation::None
             {
-                v.push(DiffCell{start: row, count: op_count, operation: op.clone()});
+                v.push(DiffCell{start: offset + row, new_start: offset + col, count: op_count, operation: op.clone()});
+                op_count = 0;
+                op = DiffOperation::None;
+            }
+
+            while row > 1 && col > 1 
+                    && self.direction(row, col, old_chars, new_chars) == DiffOperation::Transpose //transpose
+            {                       
+                row -= 2;
+                col -= 2;     
+                op_count += 2;        
+                op = DiffOperation::Transpose;             
+            }
+
+            if op != DiffOperation::None
+            {
+                v.push(DiffCell{start: offset + row, new_start: offset + col, count: op_count, operation: op.clone()});
                 op_count = 0;
                 op = DiffOperation::None;
             }
 
             while row > 0 && col > 0 
-                    && self.directions[(row, col)] == DiffOperation::Remove //remove
+                    && self.direction(row, col, old_chars, new_chars) == DiffOperation::Remove //remove
             {                                                                                  
-                row = row - 1;     //go UP
-                op_count = op_count+1;  
+                row -= 1;     //go UP
+                op_count += 1;  
                 op = DiffOperation::Remove;                                
             }
 
             if op != DiffOperation::None
             {
-                v.push(DiffCell{start: row, count: op_count, operation: op.clone()});
+                v.push(DiffCell{start: offset + row, new_start: offset + col, count: op_count, operation: op.clone()});
                 op_count = 0;
                 op = DiffOperation::None;
             }
 
             while row > 0 && col > 0 
-                    && self.directions[(row, col)] == DiffOperation::Insert //insert
+                    && self.direction(row, col, old_chars, new_chars) == DiffOperation::Insert //insert
             {                                                                             
-                col = col - 1;     //go LEFT
-                op_count = op_count+1; 
+                col -= 1;     //go LEFT
+                op_count += 1; 
                 op = DiffOperation::Insert;                        
             }
 
             if op != DiffOperation::None 
             {
-                v.push(DiffCell{start: row, count: op_count, operation: op.clone()});               
+                v.push(DiffCell{start: offset + row, new_start: offset + col, count: op_count, operation: op.clone()});               
             }
 
         }      
+
+        (row, col)
     }
 
     
@@ -302,7 +996,7 @@ mod tests
        let _new   = String::from("");       
        let mut sd   = StringDiff::new();        
        let res = sd.get_diff(&_old, &_new);
-       assert_eq!(res.len() == 0, true);
+       assert!(res.is_empty());
     }
 
     #[test]
@@ -312,7 +1006,7 @@ mod tests
        let _new   = String::from("Hello world");
        let mut sd   = StringDiff::new(); 
        let res = sd.get_diff(&_old, &_new);
-       assert_eq!(res.len() == 0, true);
+       assert!(res.is_empty());
     }
 
 
@@ -496,4 +1190,448 @@ mod tests
         assert_eq!(vec[0].operation, DiffOperation::Update);
 
     }
-}
+
+    #[test]
+    fn distance()
+    {
+        let mut sd = StringDiff::new();
+
+        assert_eq!(sd.distance("kitten", "sitting"), 3);
+        assert_eq!(sd.distance("", "abc"), 3);
+        assert_eq!(sd.distance("abc", ""), 3);
+        assert_eq!(sd.distance("Hello world", "Hello world"), 0);
+        assert_eq!(sd.distance("Hallo", "Hello world"), 7);
+    }
+
+    #[test]
+    fn ratio()
+    {
+        let mut sd = StringDiff::new();
+
+        assert_eq!(sd.ratio("", ""), 1.0);
+        assert_eq!(sd.ratio("abcd", "abcd"), 1.0);
+        assert_eq!(sd.ratio("abcd", "wxyz"), 0.0);
+        assert_eq!(sd.ratio("abcd", "abce"), 0.75);
+        assert_eq!(sd.ratio("你好", "你"), 0.5);
+    }
+
+    #[test]
+    fn close_matches()
+    {
+        let mut sd = StringDiff::new();
+        let candidates = ["ape", "apple", "peach", "puppy"];
+
+        assert_eq!(sd.get_close_matches("appel", &candidates, 3, 0.6), vec!["ape", "apple"]); //equal ratio, original order
+        assert_eq!(sd.get_close_matches("apple", &candidates, 1, 0.6), vec!["apple"]);
+        assert_eq!(sd.get_close_matches("peaches", &candidates, 3, 0.6), vec!["peach"]);
+        assert!(sd.get_close_matches("zzz", &candidates, 3, 0.6).is_empty());
+    }
+
+    #[test]
+    fn update_decomposed()
+    {
+        let costs  = DiffCosts { update : 2, .. DiffCosts::default() };
+        let mut sd = StringDiff::with_costs(costs);
+        let vec = sd.get_diff("abc", "axc");
+
+        assert_eq!(vec.len(), 2);
+
+        assert_eq!(vec[0].start, 1);
+        assert_eq!(vec[0].count, 1);
+        assert_eq!(vec[0].operation, DiffOperation::Remove);
+
+        assert_eq!(vec[1].start, 1);
+        assert_eq!(vec[1].count, 1);
+        assert_eq!(vec[1].operation, DiffOperation::Insert);
+
+        assert_eq!(sd.distance("kitten", "sitting"), 5);
+    }
+
+    #[test]
+    fn transpose_characters()
+    {
+        let costs  = DiffCosts { transpose : Some(1), .. DiffCosts::default() };
+        let mut sd = StringDiff::with_costs(costs);
+        let vec = sd.get_diff("Hleol world", "Hello world");
+
+        assert_eq!(vec.len(), 1);
+        assert_eq!(vec[0].start, 1);
+        assert_eq!(vec[0].count, 4);
+        assert_eq!(vec[0].operation, DiffOperation::Transpose);
+
+        assert_eq!(sd.distance("Hleol world", "Hello world"), 2);
+        assert_eq!(sd.distance("ca", "abc"), 3);
+    }
+
+    #[test]
+    fn character_class_costs()
+    {
+        let costs  = DiffCosts { insert_cost : Some(|ch| if ch.is_whitespace() { 0 } else { 1 }), 
+                                 remove_cost : Some(|ch| if ch.is_whitespace() { 0 } else { 1 }),
+                                 update_cost : Some(|from, to| if from.eq_ignore_ascii_case(&to) { 0 } else { 1 }),
+                                 .. DiffCosts::default() };
+        let mut sd = StringDiff::with_costs(costs);
+
+        assert_eq!(sd.distance("Hello  world", "hello world "), 0);
+        assert_eq!(sd.distance("Hello world", "hallo  world"), 1);
+
+        //free whitespace moves the path far off the diagonal, bounded diff still finds it
+        let _old = format!("x{}", " ".repeat(10));
+        let _new = format!("{}x", " ".repeat(10));
+        assert_eq!(sd.distance(&_old, &_new), 0);
+        assert_eq!(sd.get_diff_bounded(&_old, &_new, 0), Some(sd.get_diff(&_old, &_new)));
+    }
+
+    #[test]
+    fn new_positions()
+    {
+        let mut sd = StringDiff::new();
+
+        let new_starts = |vec : Vec<DiffCell>| vec.iter().map(|c| c.new_start).collect::<Vec<usize>>();
+        assert_eq!(new_starts(sd.get_diff("Hleol world", "Hello world")), vec![5, 3, 1]);
+        assert_eq!(new_starts(sd.get_diff("Hallo", "Hello world")), vec![5, 1]);
+
+        //ranges of inserted characters are empty in the old string, of removed ones in the new string
+        let vec = sd.get_diff("xabc_", "abc_y");
+        assert_eq!((vec[0].old_range(), vec[0].new_range()), (5 .. 5, 4 .. 5));
+        let vec = sd.get_diff("abc_y", "xabc_");
+        assert_eq!((vec[0].old_range(), vec[0].new_range()), (4 .. 5, 5 .. 5));
+    }
+
+    #[test]
+    fn reuse_buffers()
+    {
+        let mut sd = StringDiff::new();
+        let mut cells = Vec::new();
+
+        //long comparison leaves stale values, which must not affect the next ones
+        let long_old : String = "abcdefgh".repeat(100);
+        let long_new : String = "abcxefgh".repeat(100);
+        sd.get_diff_into(&long_old, &long_new, &mut cells);
+        assert_eq!(cells.len(), 100);
+
+        for (l, r) in [("Hello world", "Hello wrld"), ("Hallo", "Hello world"), ("same", "same"), ("", "abc")].iter()
+        {
+            sd.get_diff_into(l, r, &mut cells);
+            assert_eq!(cells, StringDiff::new().get_diff(l, r));
+        }
+    }
+
+    #[test]
+    fn memory_limit()
+    {
+        let mut sd = StringDiff::new();
+        let initial = sd.memory_usage();
+
+        let long_old : String = "a".repeat(1000);
+        let long_new : String = "b".repeat(1000);
+        sd.get_diff(&long_old, &long_new);
+        assert!(sd.memory_usage() > initial);
+
+        sd.set_memory_limit(initial);
+        sd.get_diff(&long_old, &long_new);
+        assert_eq!(sd.memory_usage(), 0);
+
+        //buffers are allocated again when needed
+        assert_eq!(sd.distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn compact_matrices()
+    {
+        //directions take 2 bits per cell, costs only few rows
+        let long_old : String = "ab".repeat(1000);
+        let long_new : String = "ba".repeat(1000);
+        let cells = 2001 * 2001;
+
+        let mut sd = StringDiff::with_costs(DiffCosts { transpose : Some(1), .. DiffCosts::default() });
+        assert!(!sd.get_diff(&long_old, &long_new).is_empty());
+        assert!(sd.memory_usage() <= cells / 4 + 1 + 3 * 2001 * 4, "{}", sd.memory_usage());
+
+        //shift by one character: remove the first one, insert the last one
+        assert_eq!(sd.distance(&long_old, &long_new), 2);
+    }
+
+    ///Differences of whole strings, without stripping shared prefix and suffix
+    fn whole_diff(sd : &mut StringDiff, _old : &str, _new : &str) -> Vec<DiffCell>
+    {
+        let mut v = Vec::new();
+        let len = std::cmp::min(_old.chars().count(), _new.chars().count());
+        let old_chars : Vec<char> = _old.chars().collect();
+        let new_chars : Vec<char> = _new.chars().collect();
+        if old_chars.len() > len {
+            v.push(DiffCell { start : len, new_start : len, count : old_chars.len() - len, operation : DiffOperation::Remove });
+        }
+        else if new_chars.len() > len {
+            v.push(DiffCell { start : len, new_start : len, count : new_chars.len() - len, operation : DiffOperation::Insert });
+        }
+
+        sd.prepare_matrix(len + 1, len + 1);
+        sd.compile_matrix(&old_chars[.. len], &new_chars[.. len], None);
+        sd.fill_result(&old_chars[.. len], &new_chars[.. len], &mut v, 0);
+        v
+    }
+
+    ///Cost of differences of strings trimmed to equal length, without the tail
+    fn trimmed_cost(sd : &mut StringDiff, _old : &str, _new : &str) -> u32
+    {
+        let len = std::cmp::min(_old.chars().count(), _new.chars().count());
+        let old_chars : Vec<char> = _old.chars().take(len).collect();
+        let new_chars : Vec<char> = _new.chars().take(len).collect();
+
+        sd.prepare_matrix(len + 1, len + 1);
+        sd.compile_matrix(&old_chars, &new_chars, None);
+        sd.last_cost(len)
+    }
+
+    ///All strings of "a" and "b" up to 5 characters
+    fn short_strings() -> Vec<String>
+    {
+        let mut strings : Vec<String> = Vec::new();
+        for len in 1 ..= 5
+        {
+            for bits in 0 .. 1 << len {
+                strings.push((0 .. len).map(|i| if bits >> i & 1 == 1 { 'b' } else { 'a' }).collect());
+            }
+        }
+        strings
+    }
+
+    #[test]
+    fn strip_common_affixes()
+    {
+        let mut sd = StringDiff::new();
+        let vec = sd.get_diff("the quick brown fox", "the quack brown fox");
+        assert_eq!(vec, vec![DiffCell { start : 6, new_start : 6, count : 1, operation : DiffOperation::Update }]);
+
+        //all pairs of short strings give the same result as whole strings
+        let strings = short_strings();
+
+        let costs = vec![DiffCosts::default(),
+                         DiffCosts { update : 2, .. DiffCosts::default() },
+                         DiffCosts { transpose : Some(1), .. DiffCosts::default() }];
+        for cost in costs
+        {
+            let mut stripped = StringDiff::with_costs(cost.clone());
+            let mut whole    = StringDiff::with_costs(cost);
+            for l in &strings
+            {
+                for r in strings.iter().filter(|s| *s != l)
+                {
+                    assert_eq!(stripped.get_diff(l, r), whole_diff(&mut whole, l, r), "{} {}", l, r);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn bounded_diff()
+    {
+        let mut sd = StringDiff::new();
+        assert_eq!(sd.get_diff_bounded("kitten", "sitting", 3), Some(sd.get_diff("kitten", "sitting")));
+        assert_eq!(sd.get_diff_bounded("kitten", "sitting", 2), None);
+        assert_eq!(sd.get_diff_bounded("same", "same", 0), Some(Vec::new()));
+        assert_eq!(sd.get_diff_bounded("", "abc", 2), None);
+        assert_eq!(full_replace("", "abc"), sd.get_diff("", "abc"));
+        assert_eq!(full_replace("abcd", "xy"), sd.get_diff("abcd", "xy"));
+
+        //heavily rewritten long strings stop at the first rows
+        let long_old : String = "abcdefgh".repeat(100);
+        let long_new : String = "hgfedcba".repeat(100);
+        assert_eq!(sd.get_diff_bounded(&long_old, &long_new, 10), None);
+
+        //result is the one of get_diff, when cost of its differences is within the bound
+        let strings = short_strings();
+        let costs = vec![DiffCosts::default(),
+                         DiffCosts { update : 2, .. DiffCosts::default() },
+                         DiffCosts { insert : 2, update : 3, .. DiffCosts::default() },
+                         DiffCosts { transpose : Some(1), .. DiffCosts::default() }];
+        for cost in costs
+        {
+            let mut sd = StringDiff::with_costs(cost.clone());
+            for l in &strings
+            {
+                for r in &strings
+                {
+                    let diff = sd.get_diff(l, r);
+                    //cost of the trimmed tail and of the compared characters
+                    let len  = std::cmp::min(l.len(), r.len());
+                    let tail = (l.len() - len) as u32 * cost.remove + (r.len() - len) as u32 * cost.insert;
+                    let total = tail + trimmed_cost(&mut sd, l, r);
+
+                    for max_d in 0 .. 8
+                    {
+                        let expected = if total <= max_d { Some(diff.clone()) } else { None };
+                        assert_eq!(sd.get_diff_bounded(l, r, max_d), expected, "{} {} {}", l, r, max_d);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn timeout()
+    {
+        use std::time::{Duration,Instant};
+
+        let mut sd = StringDiff::new();
+        sd.set_timeout(Some(Duration::from_secs(0)));
+
+        //characters not compared in time are reported as updated
+        assert_eq!(sd.get_diff("abcdef", "abXYef"), vec![DiffCell { start : 2, new_start : 2, count : 2, operation : DiffOperation::Update }]);
+        assert!(sd.timed_out());
+
+        //distance is an upper bound, long strings are not compared by bit vectors
+        let long_old : String = "ab".repeat(150);
+        let long_new : String = "ba".repeat(150);
+        assert_eq!(sd.distance(&long_old, &long_new), 300);
+
+        sd.set_timeout(Some(Duration::from_secs(60)));
+        assert_eq!(sd.distance(&long_old, &long_new), 2);
+        assert!(!sd.timed_out());
+
+        //deadline applies to every comparison
+        sd.set_deadline(Some(Instant::now()));
+        assert_eq!(sd.get_diff("Hello world", "Hello wrld"), vec![
+            DiffCell { start : 10, new_start : 10, count : 1, operation : DiffOperation::Remove },
+            DiffCell { start : 7,  new_start : 7,  count : 3, operation : DiffOperation::Update },
+        ]);
+        assert!(sd.timed_out());
+
+        sd.set_deadline(None);
+        sd.set_timeout(None);
+        assert_eq!(sd.get_diff("Hello world", "Hello wrld"), StringDiff::new().get_diff("Hello world", "Hello wrld"));
+    }
+
+    fn opcode(tag : OpcodeTag, old : std::ops::Range<usize>, new : std::ops::Range<usize>) -> Opcode
+    {
+        Opcode { tag, old, new }
+    }
+
+    #[test]
+    fn opcodes()
+    {
+        let mut sd = StringDiff::new();
+        assert_eq!(sd.get_opcodes("qabxcd", "abycdf"), vec![
+            opcode(OpcodeTag::Delete,  0 .. 1, 0 .. 0),
+            opcode(OpcodeTag::Equal,   1 .. 3, 0 .. 2),
+            opcode(OpcodeTag::Replace, 3 .. 4, 2 .. 3),
+            opcode(OpcodeTag::Equal,   4 .. 6, 3 .. 5),
+            opcode(OpcodeTag::Insert,  6 .. 6, 5 .. 6),
+        ]);
+        assert!(sd.get_opcodes("", "").is_empty());
+        assert_eq!(sd.get_opcodes("", "ab"), vec![opcode(OpcodeTag::Insert, 0 .. 0, 0 .. 2)]);
+        assert_eq!(sd.get_opcodes("abc", "abc"), vec![opcode(OpcodeTag::Equal, 0 .. 3, 0 .. 3)]);
+
+        //transposed characters are replaced
+        let mut sd = StringDiff::with_costs(DiffCosts { transpose : Some(1), .. DiffCosts::default() });
+        assert_eq!(sd.get_opcodes("Hleol", "Hello"), vec![
+            opcode(OpcodeTag::Equal,   0 .. 1, 0 .. 1),
+            opcode(OpcodeTag::Replace, 1 .. 5, 1 .. 5),
+        ]);
+
+        //not compared characters are replaced on timeout
+        let mut sd = StringDiff::new();
+        sd.set_timeout(Some(std::time::Duration::from_secs(0)));
+        assert_eq!(sd.get_opcodes("abcdef", "abXYZef"), vec![
+            opcode(OpcodeTag::Equal,   0 .. 2, 0 .. 2),
+            opcode(OpcodeTag::Replace, 2 .. 4, 2 .. 5),
+            opcode(OpcodeTag::Equal,   4 .. 6, 5 .. 7),
+        ]);
+    }
+
+    #[test]
+    fn grouped_opcodes()
+    {
+        let mut sd = StringDiff::new();
+        let groups = sd.get_grouped_opcodes("abcdefghijklmnop", "abcXefghijklmnoY", 2);
+        assert_eq!(groups, vec![
+            vec![opcode(OpcodeTag::Equal,   1 .. 3,   1 .. 3),
+                 opcode(OpcodeTag::Replace, 3 .. 4,   3 .. 4),
+                 opcode(OpcodeTag::Equal,   4 .. 6,   4 .. 6)],
+            vec![opcode(OpcodeTag::Equal,   13 .. 15, 13 .. 15),
+                 opcode(OpcodeTag::Replace, 15 .. 16, 15 .. 16)],
+        ]);
+
+        //context of both changes overlaps
+        let groups = sd.get_grouped_opcodes("abcdefghijklmnop", "abcXefghijklmnoY", 6);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0][0], opcode(OpcodeTag::Equal, 0 .. 3, 0 .. 3));
+        assert_eq!(groups[0][2], opcode(OpcodeTag::Equal, 4 .. 15, 4 .. 15));
+
+        //without context only changes are left
+        let groups = sd.get_grouped_opcodes("abcdefgh", "aXcdefYh", 0);
+        assert_eq!(groups, vec![vec![opcode(OpcodeTag::Replace, 1 .. 2, 1 .. 2)],
+                                vec![opcode(OpcodeTag::Replace, 6 .. 7, 6 .. 7)]]);
+
+        assert!(sd.get_grouped_opcodes("same", "same", 3).is_empty());
+        assert!(group_opcodes(&[], 3).is_empty());
+        assert_eq!(sd.get_grouped_opcodes("", "ab", 3), vec![vec![opcode(OpcodeTag::Insert, 0 .. 0, 0 .. 2)]]);
+    }
+
+    #[test]
+    fn opcodes_cover_strings()
+    {
+        let strings = short_strings();
+        let costs = vec![DiffCosts::default(),
+                         DiffCosts { update : 2, .. DiffCosts::default() },
+                         DiffCosts { transpose : Some(1), .. DiffCosts::default() }];
+        for cost in costs
+        {
+            let mut sd = StringDiff::with_costs(cost.clone());
+            for l in &strings
+            {
+                for r in &strings
+                {
+                    let ops = sd.get_opcodes(l, r);
+                    let (mut old_end, mut new_end) = (0, 0);
+                    let mut rebuilt = String::new();
+                    let mut changed = 0;
+                    for op in &ops
+                    {
+                        assert_eq!((op.old.start, op.new.start), (old_end, new_end), "{} {}", l, r);
+                        old_end = op.old.end;
+                        new_end = op.new.end;
+                        if op.tag == OpcodeTag::Equal {
+                            assert_eq!(&l[op.old.clone()], &r[op.new.clone()], "{} {}", l, r);
+                        }
+                        else {
+                            changed += std::cmp::max(op.old.len(), op.new.len());
+                        }
+                        rebuilt.push_str(&r[op.new.clone()]);
+                    }
+                    assert_eq!((old_end, new_end), (l.len(), r.len()), "{} {}", l, r);
+                    assert_eq!(&rebuilt, r);
+
+                    //alignment is optimal: with unit costs every changed character costs 1
+                    if cost.insert == 1 && cost.update == 1 && cost.transpose.is_none() {
+                        assert_eq!(changed as u32, sd.distance(l, r), "{} {}", l, r);
+                    }
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip()
+    {
+        extern crate serde_json;
+
+        let mut sd = StringDiff::with_costs(DiffCosts { transpose : Some(1), .. DiffCosts::default() });
+        let vec = sd.get_diff("Hleol wrld!", "Hello world");
+
+        let json = serde_json::to_string(&vec).unwrap();
+        let back : Vec<DiffCell> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, vec);
+
+        //wire representation is stable
+        let cell = DiffCell { start : 1, new_start : 1, count : 2, operation : DiffOperation::Transpose };
+        assert_eq!(serde_json::to_string(&cell).unwrap(), "{\"start\":1,\"new_start\":1,\"count\":2,\"operation\":\"transpose\"}");
+        assert_eq!(serde_json::to_string(&DiffOperation::Moved { from : 1, to : 5 }).unwrap(), "{\"moved\":{\"from\":1,\"to\":5}}");
+        assert_eq!(serde_json::from_str::<DiffOperation>("\"none\"").unwrap(), DiffOperation::None);
+
+        let op = Opcode { tag : OpcodeTag::Replace, old : 1 .. 2, new : 1 .. 3 };
+        assert_eq!(serde_json::to_string(&op).unwrap(), "{\"tag\":\"replace\",\"old\":{\"start\":1,\"end\":2},\"new\":{\"start\":1,\"end\":3}}");
+    }
+}
\ No newline at end of file
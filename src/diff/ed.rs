@@ -0,0 +1,212 @@
+//! Ed script and RCS presenters, as `diff -e` and `diff -n` write them
+//!
+//! Ed script changes the old file into the new one when it is fed to `ed`. Commands
+//! are written from the end of the file to its beginning, so line numbers of the
+//! commands are not shifted by the previous ones:
+//!
+//!     5a          append after line 5
+//!     inserted
+//!     .
+//!     2,3c        change lines 2 to 3
+//!     modified
+//!     .
+//!     1d          delete line 1
+//!
+//! Inserted line consisting of single "." would end the input of `ed`, it is written as ".."
+//! and fixed by "s/.//" command.
+//!
+//! RCS format is written from the beginning, line numbers refer to the old file:
+//!
+//!     d1 1        delete 1 line starting at line 1
+//!     a5 1        append 1 line after line 5
+//!     inserted
+//!
+use std;
+use std::io::Write;
+
+use diff::difflib::DiffCell;
+use diff::hunk::{Hunk,HunkRecorder,LineKind,hunks};
+use diff::presenter::Presenter;
+
+
+/// Output format
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+pub enum EdFormat
+{
+    Ed,
+    Rcs,
+}
+
+
+pub struct EdPresenter
+{
+    writer   : Box<dyn std::io::Write>,
+    format   : EdFormat,
+    recorder : HunkRecorder,
+}
+
+impl EdPresenter
+{
+    pub fn new(_w : Box<dyn std::io::Write>, format : EdFormat) -> EdPresenter
+    {
+        EdPresenter { writer : _w, format, recorder : HunkRecorder::new() }
+    }
+
+    ///Writes ed command of the change, followed by inserted lines
+    fn write_ed(&mut self, change : &Hunk) -> std::result::Result<usize, std::io::Error>
+    {
+        let command = match (change.old_count, change.new_count)
+        {
+            (0, _) => format!("{}a\n", change.old_start),
+            (_, 0) => format!("{}d\n", range(change)),
+            _      => format!("{}c\n", range(change)),
+        };
+        let mut bytes_written = self.writer.write(command.as_bytes())?;
+        if change.new_count == 0 {
+            return Ok(bytes_written);
+        }
+
+        let mut input_open = true;
+        for line in change.lines.iter().filter(|l| l.kind == LineKind::Insert)
+        {
+            if !input_open {
+                bytes_written += self.writer.write("a\n".as_bytes())?;
+            }
+
+            if line.text == "." {
+                //close the input and fix the line, input continues after it if there are more lines
+                bytes_written += self.writer.write("..\n.\ns/.//\n".as_bytes())?;
+                input_open = false;
+            }
+            else {
+                bytes_written += self.writer.write(format!("{}\n", line.text).as_bytes())?;
+                input_open = true;
+            }
+        }
+        if input_open {
+            bytes_written += self.writer.write(".\n".as_bytes())?;
+        }
+
+        Ok(bytes_written)
+    }
+
+    ///Writes RCS delete and append commands of the change
+    fn write_rcs(&mut self, change : &Hunk) -> std::result::Result<usize, std::io::Error>
+    {
+        let mut bytes_written = 0;
+        if change.old_count > 0 {
+            bytes_written += self.writer.write(format!("d{} {}\n", change.old_start + 1, change.old_count).as_bytes())?;
+        }
+        if change.new_count > 0
+        {
+            bytes_written += self.writer.write(format!("a{} {}\n", change.old_start + change.old_count, change.new_count).as_bytes())?;
+            for line in change.lines.iter().filter(|l| l.kind == LineKind::Insert)
+            {
+                bytes_written += self.writer.write(format!("{}\n", line.text).as_bytes())?;
+            }
+        }
+
+        Ok(bytes_written)
+    }
+}
+
+impl Presenter for EdPresenter
+{
+    fn header(&mut self, _old : &str, _new : &str) -> std::result::Result<usize, std::io::Error>
+    {
+        Ok(0)
+    }
+
+    fn present(&mut self, _old : &str, _new : &str, old_line : Option<u32>, new_line : Option<u32>, result : &[DiffCell]) -> std::result::Result<usize, std::io::Error>
+    {
+        self.recorder.record(_old, _new, old_line, new_line, result);
+        Ok(0)
+    }
+
+    fn present_moved(&mut self, line : &str, _line_num : u32, _other_line_num : u32, source : bool) -> std::result::Result<usize, std::io::Error>
+    {
+        self.recorder.record_moved(line, source);
+        Ok(0)
+    }
+
+    fn present_unchanged(&mut self, line : &str, _old_line : u32, _new_line : u32) -> std::result::Result<usize, std::io::Error>
+    {
+        self.recorder.record_unchanged(line);
+        Ok(0)
+    }
+
+    fn present_message(&mut self, text : &str) -> std::result::Result<usize, std::io::Error>
+    {
+        self.writer.write(format!("{}\n", text).as_bytes())
+    }
+
+    fn footer(&mut self) -> std::result::Result<usize, std::io::Error>
+    {
+        //hunks without context are single changes
+        let changes = hunks(&self.recorder.take(), 0);
+
+        let mut bytes_written = 0;
+        match self.format
+        {
+            EdFormat::Ed  => for change in changes.iter().rev() { bytes_written += self.write_ed(change)?; },
+            EdFormat::Rcs => for change in changes.iter() { bytes_written += self.write_rcs(change)?; },
+        }
+        self.writer.flush()?;
+
+        Ok(bytes_written)
+    }
+}
+
+
+///Range of changed lines of the old file: "first,last" or single number
+fn range(change : &Hunk) -> String
+{
+    if change.old_count == 1 {
+        format!("{}", change.old_start + 1)
+    }
+    else {
+        format!("{},{}", change.old_start + 1, change.old_start + change.old_count)
+    }
+}
+
+
+#[cfg(test)]
+mod tests
+{
+    use std::rc::Rc;
+    use std::cell::RefCell;
+    use ::diff::ed::*;
+    use ::diff::presenter::SharedBuffer;
+
+    fn render(format : EdFormat) -> String
+    {
+        let buffer = Rc::new(RefCell::new(Vec::new()));
+        let mut presenter = EdPresenter::new(Box::new(SharedBuffer(buffer.clone())), format);
+
+        presenter.header("a.txt", "b.txt").unwrap();
+        presenter.present("one", "", Some(1), None, &[]).unwrap();
+        presenter.present_unchanged("two", 2, 1).unwrap();
+        presenter.present("three", "3", Some(3), Some(2), &[]).unwrap();
+        presenter.present("four", ".", Some(4), Some(3), &[]).unwrap();
+        presenter.present_unchanged("five", 5, 4).unwrap();
+        presenter.present("", "six", None, Some(5), &[]).unwrap();
+        presenter.footer().unwrap();
+
+        let out = buffer.borrow().clone();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn ed_script()
+    {
+        assert_eq!(render(EdFormat::Ed), "5a\nsix\n.\n3,4c\n3\n..\n.\ns/.//\n1d\n");
+    }
+
+    #[test]
+    fn rcs_delta()
+    {
+        assert_eq!(render(EdFormat::Rcs), "d1 1\nd3 2\na4 2\n3\n.\na5 1\nsix\n");
+    }
+}
@@ -0,0 +1,466 @@
+//! HTML presenter
+//! Writes self-contained page (inline CSS, no external assets) with inline or
+//! side-by-side view of the difference, line numbers, character level
+//! highlighting of changed lines and collapsible runs of unchanged lines between
+//! hunks (see ContextWindow), which keep 3 lines of context around changes.
+//! Hunks are written as soon as they are complete. All content of compared files is escaped.
+//! Example:
+//!
+//!   let mut presenter = HtmlPresenter::new(Box::new(std::io::stdout()), HtmlView::SideBySide);
+//!
+use std;
+use std::io::Write;
+
+use diff::difflib::{DiffCell,DiffOperation};
+use diff::presenter::{Presenter,Row,Released,ContextWindow};
+use diff::unified::DEFAULT_CONTEXT;
+
+const STYLE : &str = "
+body { font-family: sans-serif; margin: 1em; }
+h1 { font-size: 1.2em; }
+.files span { font-family: monospace; font-weight: bold; }
+table.diff { border-collapse: collapse; width: 100%; table-layout: fixed; font-family: monospace; font-size: 0.9em; }
+table.diff td { padding: 0 0.4em; vertical-align: top; white-space: pre-wrap; word-wrap: break-word; }
+table.diff td.num { width: 4em; text-align: right; color: #888; background: #f7f7f7; }
+table.diff td.mark { width: 1em; text-align: center; }
+tr.remove td.text, td.text.remove { background: #ffecec; }
+tr.insert td.text, td.text.insert { background: #eaffea; }
+tr.moved-from td.text, td.text.moved-from { background: #f3ecff; }
+tr.moved-to td.text, td.text.moved-to { background: #ecf3ff; }
+span.remove { background: #f8b4b4; }
+span.insert { background: #a6f3a6; }
+details summary { cursor: pointer; color: #555; background: #f0f0f0; font-family: monospace; font-size: 0.9em; padding: 0 0.4em; }
+";
+
+
+/// Layout of the page
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+pub enum HtmlView
+{
+    Inline,
+    SideBySide
+}
+
+
+pub struct HtmlPresenter
+{
+    writer     : Box<dyn std::io::Write>,
+    view       : HtmlView,
+    table_open : bool,
+    window     : ContextWindow, //unchanged rows far from changes are collapsed
+    hidden     : usize,         //count of hidden rows in the current run
+    first      : Option<Row>,   //first hidden row of the run, shown as is if it stays single
+}
+
+
+impl HtmlPresenter
+{
+    pub fn new(_w : Box<dyn std::io::Write>, view : HtmlView) -> HtmlPresenter
+    {
+        HtmlPresenter { writer : _w, view, table_open : false, window : ContextWindow::new(DEFAULT_CONTEXT), hidden : 0, first : None }
+    }
+
+
+    ///Writes rows as soon as they are released, runs of hidden unchanged rows between hunks
+    ///are collapsed unless it is a single row
+    fn write_released(&mut self, released : Vec<Released>) -> std::result::Result<usize, std::io::Error>
+    {
+        let mut bytes_written = 0;
+        for item in released
+        {
+            match item
+            {
+                Released::Shown(row) => {
+                    bytes_written += self.end_collapsed()?;
+                    bytes_written += self.write_row(&row)?;
+                },
+                Released::Hidden(row) => {
+                    self.hidden += 1;
+                    if self.hidden == 1 {
+                        self.first = Some(row);
+                        continue;
+                    }
+                    if let Some(first) = self.first.take()
+                    {
+                        bytes_written += self.close_table()?;
+                        bytes_written += self.writer.write("<details>\n".as_bytes())?;
+                        bytes_written += self.write_row(&first)?;
+                    }
+                    bytes_written += self.write_row(&row)?;
+                },
+                Released::Gap => {},
+            }
+        }
+
+        Ok(bytes_written)
+    }
+
+
+    ///Completes run of hidden rows. Summary is written after the rows, once their count is known,
+    ///browsers show the first summary of details on top anyway
+    fn end_collapsed(&mut self) -> std::result::Result<usize, std::io::Error>
+    {
+        let mut bytes_written = 0;
+        if let Some(first) = self.first.take() {
+            bytes_written += self.write_row(&first)?;
+        }
+        else if self.hidden > 1
+        {
+            bytes_written += self.close_table()?;
+            bytes_written += self.writer.write(format!("<summary>{} unchanged lines</summary>\n</details>\n", self.hidden).as_bytes())?;
+        }
+        self.hidden = 0;
+
+        Ok(bytes_written)
+    }
+
+
+    fn write_row(&mut self, row : &Row) -> std::result::Result<usize, std::io::Error>
+    {
+        match *row
+        {
+            Row::Unchanged { ref line, old_line, new_line } => self.unchanged_row(line, old_line, new_line),
+            Row::Changed { ref old, ref new, old_line, new_line, ref result } => self.changed_row(old, new, old_line, new_line, result),
+            Row::Moved { ref line, line_num, other_line_num, source } => self.moved_row(line, line_num, other_line_num, source),
+        }
+    }
+
+
+    fn unchanged_row(&mut self, line : &str, old_line : u32, new_line : u32) -> std::result::Result<usize, std::io::Error>
+    {
+        let text = escape(line);
+        let row = match self.view {
+            HtmlView::Inline => format!("<tr><td class=\"num\">{}</td><td class=\"num\">{}</td><td class=\"mark\"></td><td class=\"text\">{}</td></tr>\n",
+                                        old_line, new_line, text),
+            HtmlView::SideBySide => format!("<tr><td class=\"num\">{}</td><td class=\"text\">{}</td><td class=\"num\">{}</td><td class=\"text\">{}</td></tr>\n",
+                                            old_line, text, new_line, text),
+        };
+
+        self.row(&row)
+    }
+
+
+    ///Writes table row, opening the table if needed
+    fn row(&mut self, row : &str) -> std::result::Result<usize, std::io::Error>
+    {
+        let mut bytes_written = 0;
+        if !self.table_open
+        {
+            bytes_written += self.writer.write("<table class=\"diff\">\n".as_bytes())?;
+            self.table_open = true;
+        }
+        bytes_written += self.writer.write(row.as_bytes())?;
+
+        Ok(bytes_written)
+    }
+
+
+    fn close_table(&mut self) -> std::result::Result<usize, std::io::Error>
+    {
+        if !self.table_open {
+            return Ok(0);
+        }
+
+        self.table_open = false;
+        self.writer.write("</table>\n".as_bytes())
+    }
+
+
+    fn changed_row(&mut self, _old : &str, _new : &str, old_line : Option<u32>, new_line : Option<u32>, result : &[DiffCell]) -> std::result::Result<usize, std::io::Error>
+    {
+        let mut bytes_written = 0;
+
+        //plain strings are present on both sides, lines only where they exist
+        let line_based = old_line.is_some() || new_line.is_some();
+        let has_old    = !line_based || old_line.is_some();
+        let has_new    = !line_based || new_line.is_some();
+
+        //character level highlighting only makes sense for a pair of lines
+        let (old_text, new_text) = if has_old && has_new {
+            highlight(_old, _new, result)
+        }
+        else {
+            (escape(_old), escape(_new))
+        };
+
+        let old_num = old_line.map_or(String::new(), |n| n.to_string());
+        let new_num = new_line.map_or(String::new(), |n| n.to_string());
+
+        match self.view
+        {
+            HtmlView::Inline => {
+                if has_old {
+                    bytes_written += self.row(&format!("<tr class=\"remove\"><td class=\"num\">{}</td><td class=\"num\"></td><td class=\"mark\">-</td><td class=\"text\">{}</td></tr>\n",
+                                                       old_num, old_text))?;
+                }
+                if has_new {
+                    bytes_written += self.row(&format!("<tr class=\"insert\"><td class=\"num\"></td><td class=\"num\">{}</td><td class=\"mark\">+</td><td class=\"text\">{}</td></tr>\n",
+                                                       new_num, new_text))?;
+                }
+            },
+            HtmlView::SideBySide => {
+                let old_cell = if has_old { format!("<td class=\"text remove\">{}</td>", old_text) } else { String::from("<td class=\"text\"></td>") };
+                let new_cell = if has_new { format!("<td class=\"text insert\">{}</td>", new_text) } else { String::from("<td class=\"text\"></td>") };
+                bytes_written += self.row(&format!("<tr><td class=\"num\">{}</td>{}<td class=\"num\">{}</td>{}</tr>\n",
+                                                   old_num, old_cell, new_num, new_cell))?;
+            },
+        }
+
+        Ok(bytes_written)
+    }
+
+
+    fn moved_row(&mut self, line : &str, line_num : u32, other_line_num : u32, source : bool) -> std::result::Result<usize, std::io::Error>
+    {
+        let (class, title) = if source { ("moved-from", format!("moved to line {}", other_line_num)) }
+                             else { ("moved-to", format!("moved from line {}", other_line_num)) };
+        let text = escape(line);
+
+        let row = match (&self.view, source)
+        {
+            (&HtmlView::Inline, true) =>
+                format!("<tr class=\"{}\" title=\"{}\"><td class=\"num\">{}</td><td class=\"num\"></td><td class=\"mark\">&lt;</td><td class=\"text\">{}</td></tr>\n",
+                        class, title, line_num, text),
+            (&HtmlView::Inline, false) =>
+                format!("<tr class=\"{}\" title=\"{}\"><td class=\"num\"></td><td class=\"num\">{}</td><td class=\"mark\">&gt;</td><td class=\"text\">{}</td></tr>\n",
+                        class, title, line_num, text),
+            (&HtmlView::SideBySide, true) =>
+                format!("<tr title=\"{}\"><td class=\"num\">{}</td><td class=\"text {}\">{}</td><td class=\"num\"></td><td class=\"text\"></td></tr>\n",
+                        title, line_num, class, text),
+            (&HtmlView::SideBySide, false) =>
+                format!("<tr title=\"{}\"><td class=\"num\"></td><td class=\"text\"></td><td class=\"num\">{}</td><td class=\"text {}\">{}</td></tr>\n",
+                        title, line_num, class, text),
+        };
+        self.row(&row)
+    }
+}
+
+
+impl Presenter for HtmlPresenter
+{
+    fn header(&mut self, _old : &str, _new : &str) -> std::result::Result<usize, std::io::Error>
+    {
+        let mut bytes_written = self.writer.write("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n".as_bytes())?;
+        bytes_written += self.writer.write(format!("<title>Difference between {} and {}</title>\n", escape(_old), escape(_new)).as_bytes())?;
+        bytes_written += self.writer.write(format!("<style>{}</style>\n</head>\n<body>\n", STYLE).as_bytes())?;
+        bytes_written += self.writer.write("<h1>Difference between</h1>\n".as_bytes())?;
+        bytes_written += self.writer.write(format!("<p class=\"files\"><span>{}</span> - and - <span>{}</span></p>\n", escape(_old), escape(_new)).as_bytes())?;
+
+        Ok(bytes_written)
+    }
+
+
+    fn present(&mut self, _old : &str, _new : &str, old_line : Option<u32>, new_line : Option<u32>, result : &[DiffCell]) -> std::result::Result<usize, std::io::Error>
+    {
+        let released = self.window.push_change(Row::Changed { old : _old.to_string(), new : _new.to_string(), old_line, new_line, result : result.to_vec() });
+        self.write_released(released)
+    }
+
+
+    fn present_moved(&mut self, line : &str, line_num : u32, other_line_num : u32, source : bool) -> std::result::Result<usize, std::io::Error>
+    {
+        let released = self.window.push_change(Row::Moved { line : line.to_string(), line_num, other_line_num, source });
+        self.write_released(released)
+    }
+
+
+    fn present_unchanged(&mut self, line : &str, old_line : u32, new_line : u32) -> std::result::Result<usize, std::io::Error>
+    {
+        let released = self.window.push_unchanged(Row::Unchanged { line : line.to_string(), old_line, new_line });
+        self.write_released(released)
+    }
+
+
+    fn present_message(&mut self, text : &str) -> std::result::Result<usize, std::io::Error>
+    {
+        self.writer.write(format!("<p class=\"message\">{}</p>\n", escape(text)).as_bytes())
+    }
+
+
+    fn footer(&mut self) -> std::result::Result<usize, std::io::Error>
+    {
+        //unchanged lines after the last hunk are collapsed
+        let changed = self.window.is_changed();
+        let released = self.window.finish();
+        let mut bytes_written = self.write_released(released)?;
+        bytes_written += self.end_collapsed()?;
+
+        bytes_written += self.close_table()?;
+        if !changed {
+            bytes_written += self.writer.write("<p>No difference</p>\n".as_bytes())?;
+        }
+        bytes_written += self.writer.write("</body>\n</html>\n".as_bytes())?;
+        self.writer.flush()?;
+
+        Ok(bytes_written)
+    }
+}
+
+
+///Escapes text to be safely placed into HTML element or attribute
+pub fn escape(text : &str) -> String
+{
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars()
+    {
+        match ch {
+            '&'  => escaped.push_str("&amp;"),
+            '<'  => escaped.push_str("&lt;"),
+            '>'  => escaped.push_str("&gt;"),
+            '"'  => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _    => escaped.push(ch),
+        }
+    }
+
+    escaped
+}
+
+
+///Returns escaped old and new text with changed characters wrapped into spans
+fn highlight(_old : &str, _new : &str, result : &[DiffCell]) -> (String, String)
+{
+    let mut old_mask = vec![false; _old.chars().count()];
+    let mut new_mask = vec![false; _new.chars().count()];
+
+    for cell in result
+    {
+        if let DiffOperation::None | DiffOperation::Moved { .. } = cell.operation {
+            continue;
+        }
+        old_mask[cell.old_range()].fill(true);
+        new_mask[cell.new_range()].fill(true);
+    }
+
+    (spans(_old, &old_mask, "remove"), spans(_new, &new_mask, "insert"))
+}
+
+
+///Escapes text, wrapping runs of masked characters into span of the given class
+fn spans(text : &str, mask : &[bool], class : &str) -> String
+{
+    let mut out = String::with_capacity(text.len());
+    let mut in_span = false;
+    for (ch, &masked) in text.chars().zip(mask.iter())
+    {
+        if masked && !in_span {
+            out.push_str(&format!("<span class=\"{}\">", class));
+        }
+        else if !masked && in_span {
+            out.push_str("</span>");
+        }
+        in_span = masked;
+        out.push_str(&escape(&ch.to_string()));
+    }
+    if in_span {
+        out.push_str("</span>");
+    }
+
+    out
+}
+
+
+#[cfg(test)]
+mod tests
+{
+    use std::rc::Rc;
+    use std::cell::RefCell;
+    use ::diff::html::*;
+    use ::diff::difflib::StringDiff;
+    use ::diff::presenter::SharedBuffer;
+
+    fn render(view : HtmlView, unchanged : usize) -> String
+    {
+        let buffer = Rc::new(RefCell::new(Vec::new()));
+        let mut presenter = HtmlPresenter::new(Box::new(SharedBuffer(buffer.clone())), view);
+        let mut sd = StringDiff::new();
+
+        presenter.header("a<b>.txt", "b.txt").unwrap();
+        for i in 0 .. unchanged {
+            presenter.present_unchanged("same", i as u32 + 1, i as u32 + 1).unwrap();
+        }
+        let n = unchanged as u32 + 1;
+        presenter.present("let x = 1;", "let y = 1;", Some(n), Some(n), &sd.get_diff("let x = 1;", "let y = 1;")).unwrap();
+        presenter.present("<script>", "", Some(n + 1), None, &sd.get_diff("<script>", "")).unwrap();
+        presenter.footer().unwrap();
+
+        let out = buffer.borrow().clone();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn escape_content()
+    {
+        assert_eq!(escape("<a href=\"x\">'&'</a>"), "&lt;a href=&quot;x&quot;&gt;&#39;&amp;&#39;&lt;/a&gt;");
+
+        let page = render(HtmlView::Inline, 0);
+        assert!(page.contains("a&lt;b&gt;.txt"));
+        assert!(page.contains("&lt;script&gt;"));
+        assert!(!page.contains("<script>"));
+    }
+
+    #[test]
+    fn intra_line_highlight()
+    {
+        let page = render(HtmlView::SideBySide, 0);
+        assert!(page.contains("let <span class=\"remove\">x</span> = 1;"));
+        assert!(page.contains("let <span class=\"insert\">y</span> = 1;"));
+    }
+
+    #[test]
+    fn collapse_unchanged()
+    {
+        let page = render(HtmlView::Inline, 10);
+        assert!(page.contains("<details>\n<table class=\"diff\">\n"));
+        assert!(page.contains("</table>\n<summary>7 unchanged lines</summary>\n</details>\n<table class=\"diff\">\n"));
+        assert!(page.ends_with("</body>\n</html>\n"));
+
+        let page = render(HtmlView::Inline, 3);
+        assert!(!page.contains("<details>"));
+    }
+
+    #[test]
+    fn collapse_between_changes()
+    {
+        let buffer = Rc::new(RefCell::new(Vec::new()));
+        let mut presenter = HtmlPresenter::new(Box::new(SharedBuffer(buffer.clone())), HtmlView::SideBySide);
+        presenter.header("a.txt", "b.txt").unwrap();
+        presenter.present("", "first", None, Some(1), &[]).unwrap();
+        for i in 1 .. 11 {
+            presenter.present_unchanged("same", i, i + 1).unwrap();
+        }
+        presenter.present("last", "", Some(11), None, &[]).unwrap();
+        presenter.footer().unwrap();
+
+        //3 lines of context after the first change and before the last one
+        let page = String::from_utf8(buffer.borrow().clone()).unwrap();
+        assert_eq!(page.matches("<details>").count(), 1);
+        assert!(page.contains("<summary>4 unchanged lines</summary>"));
+        assert!(!page.contains("No difference"));
+    }
+
+    #[test]
+    fn hunk_written_before_footer()
+    {
+        let buffer = Rc::new(RefCell::new(Vec::new()));
+        let mut presenter = HtmlPresenter::new(Box::new(SharedBuffer(buffer.clone())), HtmlView::Inline);
+        presenter.present("", "first", None, Some(1), &[]).unwrap();
+        for i in 1 .. 6 {
+            presenter.present_unchanged("same", i, i + 1).unwrap();
+        }
+
+        //context after the change is written, the rest waits for the next change or footer
+        let page = String::from_utf8(buffer.borrow().clone()).unwrap();
+        assert!(page.contains("first"));
+        assert_eq!(page.matches("<tr>").count(), 3);
+        assert!(!page.contains("<details>"));
+
+        //2 rows left after the context are collapsed, a single one would be shown as is
+        presenter.footer().unwrap();
+        let page = String::from_utf8(buffer.borrow().clone()).unwrap();
+        assert_eq!(page.matches("<tr>").count(), 5);
+        assert!(page.contains("<summary>2 unchanged lines</summary>"));
+    }
+}
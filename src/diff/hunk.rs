@@ -0,0 +1,374 @@
+//! Hunks: changed lines with surrounding context, shared by unified and context formats
+//! Presenters receive the difference line by line, HunkRecorder collects it back into
+//! the line script of the whole input (removed lines of a change are put before
+//! the inserted ones) and `hunks` groups that script into hunks. Changes separated
+//! by no more than 2 * context unchanged lines share one hunk.
+use std;
+
+use diff::difflib::{DiffCell,DiffOperation,Opcode,OpcodeTag,group_opcodes};
+use diff::linediff::LineCell;
+
+
+/// Kind of line of the script
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum LineKind
+{
+    Context,
+    Remove,
+    Insert,
+}
+
+
+/// Line of the script: unchanged, removed from old input or inserted into new one
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ScriptLine
+{
+    pub kind : LineKind,
+    pub text : String,
+}
+
+impl ScriptLine
+{
+    pub fn new(kind : LineKind, text : &str) -> ScriptLine
+    {
+        ScriptLine { kind, text : text.to_string() }
+    }
+}
+
+
+/// Changed lines with their context.
+/// old_start, new_start: 0 based index of the first line of the hunk in old and new input,
+/// when count is 0 it is the index of the line the hunk is placed before
+/// old_no_newline, new_no_newline: The last line of the side ends the input and has no
+/// line terminator ("\ No newline at end of file" of the patch)
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+#[derive(Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Hunk
+{
+    pub old_start : usize,
+    pub old_count : usize,
+    pub new_start : usize,
+    pub new_count : usize,
+    pub lines     : Vec<ScriptLine>,
+    pub old_no_newline : bool,
+    pub new_no_newline : bool,
+}
+
+impl Hunk
+{
+    /// Lines of the hunk as line edit script, positioned in the whole old and new input
+    #[allow(dead_code)]
+    pub fn cells(&self) -> Vec<LineCell>
+    {
+        let mut cells : Vec<LineCell> = Vec::new();
+        let (mut old_idx, mut new_idx) = (self.old_start, self.new_start);
+        for line in &self.lines
+        {
+            let operation = match line.kind
+            {
+                LineKind::Context => DiffOperation::None,
+                LineKind::Remove  => DiffOperation::Remove,
+                LineKind::Insert  => DiffOperation::Insert,
+            };
+
+            match cells.last_mut()
+            {
+                Some(last) if last.operation == operation => last.count += 1,
+                _ => cells.push(LineCell { old_start : old_idx, new_start : new_idx, count : 1, operation }),
+            }
+            advance(&line.kind, &mut old_idx, &mut new_idx);
+        }
+
+        cells
+    }
+
+    /// Hunk changing new input back into old one, removed lines of a change stay first
+    pub fn inverted(&self) -> Hunk
+    {
+        let mut lines = Vec::with_capacity(self.lines.len());
+        let mut inserted = Vec::new();
+        for line in &self.lines
+        {
+            match line.kind
+            {
+                LineKind::Context => {
+                    lines.append(&mut inserted);
+                    lines.push(line.clone());
+                },
+                LineKind::Remove => inserted.push(ScriptLine { kind : LineKind::Insert, text : line.text.clone() }),
+                LineKind::Insert => lines.push(ScriptLine { kind : LineKind::Remove, text : line.text.clone() }),
+            }
+        }
+        lines.append(&mut inserted);
+
+        Hunk { old_start : self.new_start, old_count : self.new_count, new_start : self.old_start, new_count : self.old_count, lines,
+               old_no_newline : self.new_no_newline, new_no_newline : self.old_no_newline }
+    }
+}
+
+
+/// Collects lines passed to a presenter into the script of the whole input
+pub struct HunkRecorder
+{
+    lines   : Vec<ScriptLine>,
+    pending : Vec<ScriptLine>, //inserted lines of the current change, written after removed ones
+    old_no_newline : bool,
+    new_no_newline : bool,
+}
+
+impl HunkRecorder
+{
+    pub fn new() -> HunkRecorder
+    {
+        HunkRecorder { lines : Vec::new(), pending : Vec::new(), old_no_newline : false, new_no_newline : false }
+    }
+
+    /// Sets whether the last line of old and new input has no line terminator
+    pub fn set_no_newline(&mut self, old_no_newline : bool, new_no_newline : bool)
+    {
+        self.old_no_newline = old_no_newline;
+        self.new_no_newline = new_no_newline;
+    }
+
+    /// Records changed line, as received by Presenter::present. Plain strings (no line numbers)
+    /// are recorded as single line, context when they are equal.
+    pub fn record(&mut self, _old : &str, _new : &str, old_line : Option<u32>, new_line : Option<u32>, result : &[DiffCell])
+    {
+        if old_line.is_none() && new_line.is_none()
+        {
+            if result.is_empty() {
+                self.record_unchanged(_old);
+            }
+            else {
+                self.lines.push(ScriptLine::new(LineKind::Remove, _old));
+                self.pending.push(ScriptLine::new(LineKind::Insert, _new));
+            }
+            return;
+        }
+
+        if old_line.is_some() {
+            self.lines.push(ScriptLine::new(LineKind::Remove, _old));
+        }
+        if new_line.is_some() {
+            self.pending.push(ScriptLine::new(LineKind::Insert, _new));
+        }
+    }
+
+    /// Records line of moved block: removed at the source, inserted at the destination
+    pub fn record_moved(&mut self, line : &str, source : bool)
+    {
+        if source {
+            self.lines.push(ScriptLine::new(LineKind::Remove, line));
+        }
+        else {
+            self.pending.push(ScriptLine::new(LineKind::Insert, line));
+        }
+    }
+
+    pub fn record_unchanged(&mut self, line : &str)
+    {
+        self.lines.append(&mut self.pending);
+        self.lines.push(ScriptLine::new(LineKind::Context, line));
+    }
+
+    /// Recorded script, takes it out of the recorder
+    pub fn take(&mut self) -> Vec<ScriptLine>
+    {
+        self.lines.append(&mut self.pending);
+        std::mem::take(&mut self.lines)
+    }
+
+    /// Hunks of the recorded script (see hunks), takes it out of the recorder.
+    /// The last hunk is marked when it reaches the end of a side without line terminator
+    pub fn take_hunks(&mut self, context : usize) -> Vec<Hunk>
+    {
+        let script = self.take();
+        let mut result = hunks(&script, context);
+        if let Some(last) = result.last_mut()
+        {
+            let old_count = script.iter().filter(|l| l.kind != LineKind::Insert).count();
+            let new_count = script.iter().filter(|l| l.kind != LineKind::Remove).count();
+            last.old_no_newline = self.old_no_newline && last.old_start + last.old_count == old_count;
+            last.new_no_newline = self.new_no_newline && last.new_start + last.new_count == new_count;
+        }
+
+        result
+    }
+}
+
+impl Default for HunkRecorder
+{
+    fn default() -> HunkRecorder
+    {
+        HunkRecorder::new()
+    }
+}
+
+
+/// Groups changed lines of the script into hunks with `context` unchanged lines around them
+/// (see group_opcodes), removed lines of every change are put before the inserted ones
+pub fn hunks(script : &[ScriptLine], context : usize) -> Vec<Hunk>
+{
+    let old_lines : Vec<&str> = script.iter().filter(|l| l.kind != LineKind::Insert).map(|l| l.text.as_str()).collect();
+    let new_lines : Vec<&str> = script.iter().filter(|l| l.kind != LineKind::Remove).map(|l| l.text.as_str()).collect();
+
+    group_opcodes(&opcodes(script), context).iter().map(|group| {
+        let (first, last) = (&group[0], &group[group.len() - 1]);
+        let mut lines = Vec::new();
+        for op in group
+        {
+            if op.tag == OpcodeTag::Equal {
+                lines.extend(op.old.clone().map(|i| ScriptLine::new(LineKind::Context, old_lines[i])));
+            }
+            else {
+                lines.extend(op.old.clone().map(|i| ScriptLine::new(LineKind::Remove, old_lines[i])));
+                lines.extend(op.new.clone().map(|i| ScriptLine::new(LineKind::Insert, new_lines[i])));
+            }
+        }
+
+        Hunk { old_start : first.old.start, old_count : last.old.end - first.old.start,
+               new_start : first.new.start, new_count : last.new.end - first.new.start, lines, .. Hunk::default() }
+    }).collect()
+}
+
+
+/// Opcodes of the script: runs of unchanged lines and runs of changed lines between them
+fn opcodes(script : &[ScriptLine]) -> Vec<Opcode>
+{
+    let mut ops : Vec<Opcode> = Vec::new();
+    let (mut old_idx, mut new_idx) = (0, 0);
+    for line in script
+    {
+        let (old_start, new_start) = (old_idx, new_idx);
+        advance(&line.kind, &mut old_idx, &mut new_idx);
+
+        let equal = line.kind == LineKind::Context;
+        match ops.last_mut()
+        {
+            Some(last) if (last.tag == OpcodeTag::Equal) == equal => {
+                last.old.end = old_idx;
+                last.new.end = new_idx;
+            },
+            _ => ops.push(Opcode { tag : OpcodeTag::Equal, old : old_start .. old_idx, new : new_start .. new_idx }),
+        }
+
+        //changed run is tagged by the sides it covers
+        if let Some(last) = ops.last_mut().filter(|_| !equal)
+        {
+            last.tag = match (last.old.is_empty(), last.new.is_empty())
+            {
+                (false, false) => OpcodeTag::Replace,
+                (false, true)  => OpcodeTag::Delete,
+                _              => OpcodeTag::Insert,
+            };
+        }
+    }
+
+    ops
+}
+
+
+fn advance(kind : &LineKind, old_idx : &mut usize, new_idx : &mut usize)
+{
+    match *kind
+    {
+        LineKind::Context => { *old_idx += 1; *new_idx += 1; },
+        LineKind::Remove  => *old_idx += 1,
+        LineKind::Insert  => *new_idx += 1,
+    }
+}
+
+
+#[cfg(test)]
+mod tests
+{
+    use ::diff::hunk::*;
+
+    fn script(spec : &str) -> Vec<ScriptLine>
+    {
+        //" a" context, "-a" removed, "+a" inserted
+        spec.split('|').map(|l| {
+            let kind = match &l[.. 1] { "-" => LineKind::Remove, "+" => LineKind::Insert, _ => LineKind::Context };
+            ScriptLine::new(kind, &l[1 ..])
+        }).collect()
+    }
+
+    #[test]
+    fn recorder_puts_removed_lines_first()
+    {
+        let mut rec = HunkRecorder::new();
+        rec.record_unchanged("a");
+        rec.record("b", "x", Some(2), Some(2), &[]);
+        rec.record("c", "y", Some(3), Some(3), &[]);
+        rec.record("", "z", None, Some(4), &[]);
+        rec.record_unchanged("d");
+
+        assert_eq!(rec.take(), script(" a|-b|-c|+x|+y|+z| d"));
+    }
+
+    #[test]
+    fn group_hunks()
+    {
+        let lines = script(" 1| 2| 3| 4|-5|+x| 6| 7| 8| 9| 10| 11| 12| 13|+y");
+        let result = hunks(&lines, 2);
+
+        assert_eq!(result.len(), 2);
+        assert_eq!((result[0].old_start, result[0].old_count, result[0].new_start, result[0].new_count), (2, 5, 2, 5));
+        assert_eq!(result[0].lines, script(" 3| 4|-5|+x| 6| 7"));
+        assert_eq!((result[1].old_start, result[1].old_count, result[1].new_start, result[1].new_count), (11, 2, 11, 3));
+
+        //close changes share the hunk
+        let result = hunks(&lines, 4);
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].lines, lines);
+    }
+
+    #[test]
+    fn cells_and_inversion()
+    {
+        let hunk = Hunk { old_start : 4, old_count : 3, new_start : 6, new_count : 4, lines : script(" a|-b|+x|+y| c"), new_no_newline : true, .. Hunk::default() };
+        assert_eq!(hunk.cells(), vec![
+            LineCell { old_start : 4, new_start : 6, count : 1, operation : DiffOperation::None },
+            LineCell { old_start : 5, new_start : 7, count : 1, operation : DiffOperation::Remove },
+            LineCell { old_start : 6, new_start : 7, count : 2, operation : DiffOperation::Insert },
+            LineCell { old_start : 6, new_start : 9, count : 1, operation : DiffOperation::None },
+        ]);
+
+        let inverted = hunk.inverted();
+        assert_eq!(inverted, Hunk { old_start : 6, old_count : 4, new_start : 4, new_count : 3, lines : script(" a|-x|-y|+b| c"),
+                                     old_no_newline : true, new_no_newline : false });
+        assert_eq!(inverted.inverted(), hunk);
+    }
+
+    #[test]
+    fn no_changes()
+    {
+        assert!(hunks(&script(" a| b"), 3).is_empty());
+        assert!(hunks(&[], 3).is_empty());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip()
+    {
+        extern crate serde_json;
+
+        let hunk = Hunk { old_start : 4, old_count : 2, new_start : 4, new_count : 2, lines : script(" a|-b|+c"), new_no_newline : true, .. Hunk::default() };
+        let json = serde_json::to_string(&hunk).unwrap();
+        let back : Hunk = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, hunk);
+
+        //wire representation is stable
+        assert_eq!(serde_json::to_string(&hunk.lines[1]).unwrap(), "{\"kind\":\"remove\",\"text\":\"b\"}");
+    }
+}
@@ -0,0 +1,389 @@
+//! JSON presenter
+//! Writes machine readable difference, either as single JSON document
+//! or as newline-delimited JSON (one object per line) for streaming of large inputs.
+//!
+//! Schema of the single document:
+//!
+//!   {
+//!     "old": "a.txt",                   compared files (or strings)
+//!     "new": "b.txt",
+//!     "hunks": [ hunk, ... ],
+//!     "stats": stats
+//!   }
+//!
+//! hunk - consecutive changed lines, not separated by unchanged ones:
+//!
+//!   {
+//!     "old_start": 12, "old_count": 2,  first line number and count of lines in old file,
+//!     "new_start": 12, "new_count": 3,  the same for new file, start is null when count is 0
+//!     "lines": [ line, ... ]            or when plain strings are compared
+//!   }
+//!
+//! line - changed line, or part of moved block:
+//!
+//!   { "kind": "change", "old_line": 12, "new_line": 12, "old_text": "...", "new_text": "...", "operations": [ operation, ... ] }
+//!   { "kind": "moved", "source": true, "old_line": 1, "new_line": 40, "text": "..." }
+//!
+//!   old_line/new_line of a change are null for inserted/removed line. Moved line is at old_line
+//!   when "source" is true and at new_line otherwise, the other number is where it is moved to or from.
+//!
+//! operation - character level difference of the change:
+//!
+//!   {
+//!     "op": "insert" | "remove" | "update" | "transpose",
+//!     "old": { "start": 0, "end": 2, "byte_start": 0, "byte_end": 2 },    half-open ranges in characters and bytes,
+//!     "new": { "start": 0, "end": 3, "byte_start": 0, "byte_end": 3 },    empty range for the side without characters
+//!     "old_text": "13", "new_text": "thi"
+//!   }
+//!
+//! stats:
+//!
+//!   { "hunks": 1, "lines_inserted": 1, "lines_removed": 0, "lines_changed": 1, "lines_moved": 0,
+//!     "chars_inserted": 1, "chars_removed": 0, "chars_updated": 2 }
+//!
+//!   differing strings (-s1, -s2) count as one changed line
+//!
+//! Newline-delimited mode writes the same objects, one per line, tagged with "type":
+//!
+//!   { "type": "header", "old": "a.txt", "new": "b.txt" }
+//!   { "type": "hunk", "old_start": ..., "lines": [ ... ] }
+//!   { "type": "stats", "hunks": ... }
+//!
+//! Messages about compared directories ("Only in a: x.txt") are written between them:
+//!
+//!   { "type": "message", "text": "Only in a: x.txt" }
+//!
+use std;
+use std::io::Write;
+use std::ops::Range;
+
+use diff::difflib::{DiffCell,DiffOperation};
+use diff::presenter::Presenter;
+
+
+/// Output mode
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+pub enum JsonMode
+{
+    Document,
+    Lines
+}
+
+
+///Summary of the whole difference
+#[derive(Default)]
+struct Stats
+{
+    hunks          : usize,
+    lines_inserted : usize,
+    lines_removed  : usize,
+    lines_changed  : usize,
+    lines_moved    : usize,
+    chars_inserted : usize,
+    chars_removed  : usize,
+    chars_updated  : usize
+}
+
+///Hunk being collected
+#[derive(Default)]
+struct Hunk
+{
+    old_start : Option<u32>,
+    old_count : u32,
+    new_start : Option<u32>,
+    new_count : u32,
+    lines     : Vec<String> //already serialized lines
+}
+
+
+pub struct JsonPresenter
+{
+    writer : Box<dyn std::io::Write>,
+    mode   : JsonMode,
+    hunk   : Option<Hunk>,
+    stats  : Stats
+}
+
+
+impl JsonPresenter
+{
+    pub fn new(_w : Box<dyn std::io::Write>, mode : JsonMode) -> JsonPresenter
+    {
+        JsonPresenter { writer : _w, mode, hunk : None, stats : Stats::default() }
+    }
+
+
+    ///Adds serialized line to the current hunk, counting the line at the sides it exists on
+    fn add_line(&mut self, line : String, old_line : Option<u32>, new_line : Option<u32>)
+    {
+        let hunk = self.hunk.get_or_insert_with(Hunk::default);
+        if let Some(n) = old_line {
+            hunk.old_start = Some(hunk.old_start.map_or(n, |s| s.min(n)));
+            hunk.old_count += 1;
+        }
+        if let Some(n) = new_line {
+            hunk.new_start = Some(hunk.new_start.map_or(n, |s| s.min(n)));
+            hunk.new_count += 1;
+        }
+        hunk.lines.push(line);
+    }
+
+
+    ///Writes the current hunk, if any
+    fn flush_hunk(&mut self) -> std::result::Result<usize, std::io::Error>
+    {
+        let hunk = match self.hunk.take() {
+            Some(hunk) => hunk,
+            None => return Ok(0),
+        };
+
+        let body = format!("\"old_start\":{},\"old_count\":{},\"new_start\":{},\"new_count\":{},\"lines\":[{}]",
+                           number(hunk.old_start), hunk.old_count, number(hunk.new_start), hunk.new_count, hunk.lines.join(","));
+
+        let out = match self.mode {
+            JsonMode::Document if self.stats.hunks == 0 => format!("{{{}}}", body),
+            JsonMode::Document => format!(",{{{}}}", body),
+            JsonMode::Lines => format!("{{\"type\":\"hunk\",{}}}\n", body),
+        };
+        self.stats.hunks += 1;
+
+        self.writer.write(out.as_bytes())
+    }
+}
+
+
+impl Presenter for JsonPresenter
+{
+    fn header(&mut self, _old : &str, _new : &str) -> std::result::Result<usize, std::io::Error>
+    {
+        let out = match self.mode {
+            JsonMode::Document => format!("{{\"old\":{},\"new\":{},\"hunks\":[", escape(_old), escape(_new)),
+            JsonMode::Lines => format!("{{\"type\":\"header\",\"old\":{},\"new\":{}}}\n", escape(_old), escape(_new)),
+        };
+
+        self.writer.write(out.as_bytes())
+    }
+
+
+    fn present(&mut self, _old : &str, _new : &str, old_line : Option<u32>, new_line : Option<u32>, result : &[DiffCell]) -> std::result::Result<usize, std::io::Error>
+    {
+        //inserted or removed blank line has no character level difference, but is still a change
+        if result.is_empty() && old_line.is_some() == new_line.is_some() {
+            return Ok(0);
+        }
+
+        //compared strings have no line numbers, they count as one changed line
+        if old_line.is_some() == new_line.is_some() {
+            self.stats.lines_changed += 1;
+        }
+        else if old_line.is_some() {
+            self.stats.lines_removed += 1;
+        }
+        else if new_line.is_some() {
+            self.stats.lines_inserted += 1;
+        }
+
+        let mut operations = Vec::with_capacity(result.len());
+        for cell in result.iter().rev()
+        {
+            let op = match cell.operation {
+                DiffOperation::Insert    => { self.stats.chars_inserted += cell.count; "insert" },
+                DiffOperation::Remove    => { self.stats.chars_removed += cell.count; "remove" },
+                DiffOperation::Update    => { self.stats.chars_updated += cell.count; "update" },
+                DiffOperation::Transpose => { self.stats.chars_updated += cell.count; "transpose" },
+                _ => continue,
+            };
+
+            let (old, new) = (cell.old_range(), cell.new_range());
+            operations.push(format!("{{\"op\":\"{}\",\"old\":{},\"new\":{},\"old_text\":{},\"new_text\":{}}}",
+                                    op, range(_old, &old), range(_new, &new),
+                                    escape(&substring(_old, &old)), escape(&substring(_new, &new))));
+        }
+
+        let line = format!("{{\"kind\":\"change\",\"old_line\":{},\"new_line\":{},\"old_text\":{},\"new_text\":{},\"operations\":[{}]}}",
+                           number(old_line), number(new_line), escape(_old), escape(_new), operations.join(","));
+        self.add_line(line, old_line, new_line);
+
+        Ok(0)
+    }
+
+
+    fn present_moved(&mut self, line : &str, line_num : u32, other_line_num : u32, source : bool) -> std::result::Result<usize, std::io::Error>
+    {
+        let (old_line, new_line) = if source { (line_num, other_line_num) } else { (other_line_num, line_num) };
+        if source {
+            self.stats.lines_moved += 1;
+        }
+
+        let out = format!("{{\"kind\":\"moved\",\"source\":{},\"old_line\":{},\"new_line\":{},\"text\":{}}}",
+                          source, old_line, new_line, escape(line));
+        if source {
+            self.add_line(out, Some(line_num), None);
+        }
+        else {
+            self.add_line(out, None, Some(line_num));
+        }
+
+        Ok(0)
+    }
+
+
+    fn present_unchanged(&mut self, _line : &str, _old_line : u32, _new_line : u32) -> std::result::Result<usize, std::io::Error>
+    {
+        self.flush_hunk()
+    }
+
+
+    fn present_message(&mut self, text : &str) -> std::result::Result<usize, std::io::Error>
+    {
+        self.writer.write(format!("{{\"type\":\"message\",\"text\":{}}}\n", escape(text)).as_bytes())
+    }
+
+
+    fn footer(&mut self) -> std::result::Result<usize, std::io::Error>
+    {
+        let mut bytes_written = self.flush_hunk()?;
+
+        let s = &self.stats;
+        let stats = format!("\"hunks\":{},\"lines_inserted\":{},\"lines_removed\":{},\"lines_changed\":{},\"lines_moved\":{},\"chars_inserted\":{},\"chars_removed\":{},\"chars_updated\":{}",
+                            s.hunks, s.lines_inserted, s.lines_removed, s.lines_changed, s.lines_moved, s.chars_inserted, s.chars_removed, s.chars_updated);
+        let out = match self.mode {
+            JsonMode::Document => format!("],\"stats\":{{{}}}}}\n", stats),
+            JsonMode::Lines => format!("{{\"type\":\"stats\",{}}}\n", stats),
+        };
+        bytes_written += self.writer.write(out.as_bytes())?;
+        self.writer.flush()?;
+
+        Ok(bytes_written)
+    }
+}
+
+
+///Returns JSON string literal of the text, quotes included
+pub fn escape(text : &str) -> String
+{
+    let mut escaped = String::with_capacity(text.len() + 2);
+    escaped.push('"');
+    for ch in text.chars()
+    {
+        match ch {
+            '"'  => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+
+    escaped
+}
+
+
+///JSON number or null
+fn number(n : Option<u32>) -> String
+{
+    n.map_or(String::from("null"), |n| n.to_string())
+}
+
+
+///Serialized range of characters, with byte offsets of its ends
+fn range(text : &str, chars : &Range<usize>) -> String
+{
+    format!("{{\"start\":{},\"end\":{},\"byte_start\":{},\"byte_end\":{}}}",
+            chars.start, chars.end, byte_offset(text, chars.start), byte_offset(text, chars.end))
+}
+
+
+///Byte offset of the character with index `char_idx`, length of the text past its end
+fn byte_offset(text : &str, char_idx : usize) -> usize
+{
+    text.char_indices().nth(char_idx).map_or(text.len(), |(b, _)| b)
+}
+
+
+fn substring(text : &str, chars : &Range<usize>) -> String
+{
+    text.chars().skip(chars.start).take(chars.len()).collect()
+}
+
+
+#[cfg(test)]
+mod tests
+{
+    use std::rc::Rc;
+    use std::cell::RefCell;
+    use ::diff::json::*;
+    use ::diff::difflib::StringDiff;
+    use ::diff::presenter::SharedBuffer;
+
+    fn render(mode : JsonMode) -> String
+    {
+        let buffer = Rc::new(RefCell::new(Vec::new()));
+        let mut presenter = JsonPresenter::new(Box::new(SharedBuffer(buffer.clone())), mode);
+        let mut sd = StringDiff::new();
+
+        presenter.header("a.txt", "b\"c.txt").unwrap();
+        presenter.present_unchanged("same", 1, 1).unwrap();
+        presenter.present("ab", "aé", Some(2), Some(2), &sd.get_diff("ab", "aé")).unwrap();
+        presenter.present("", "new", None, Some(3), &sd.get_diff("", "new")).unwrap();
+        presenter.present_unchanged("same", 3, 4).unwrap();
+        presenter.present_moved("moved", 4, 5, true).unwrap();
+        presenter.footer().unwrap();
+
+        let out = buffer.borrow().clone();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn escape_strings()
+    {
+        assert_eq!(escape("a\"b\\c\nd\u{1}"), "\"a\\\"b\\\\c\\nd\\u0001\"");
+        assert_eq!(escape("你好"), "\"你好\"");
+    }
+
+    #[test]
+    fn document()
+    {
+        let out = render(JsonMode::Document);
+
+        assert!(out.starts_with("{\"old\":\"a.txt\",\"new\":\"b\\\"c.txt\",\"hunks\":[{\"old_start\":2,\"old_count\":1,\"new_start\":2,\"new_count\":2,"));
+        assert!(out.contains("{\"op\":\"update\",\"old\":{\"start\":1,\"end\":2,\"byte_start\":1,\"byte_end\":2},\"new\":{\"start\":1,\"end\":2,\"byte_start\":1,\"byte_end\":3},\"old_text\":\"b\",\"new_text\":\"é\"}"));
+        assert!(out.contains("{\"kind\":\"change\",\"old_line\":null,\"new_line\":3,\"old_text\":\"\",\"new_text\":\"new\""));
+        assert!(out.contains("},{\"old_start\":4,\"old_count\":1,\"new_start\":null,\"new_count\":0,\"lines\":[{\"kind\":\"moved\",\"source\":true,\"old_line\":4,\"new_line\":5,\"text\":\"moved\"}]}"));
+        assert!(out.ends_with("],\"stats\":{\"hunks\":2,\"lines_inserted\":1,\"lines_removed\":0,\"lines_changed\":1,\"lines_moved\":1,\"chars_inserted\":3,\"chars_removed\":0,\"chars_updated\":1}}\n"));
+    }
+
+    #[test]
+    fn strings_count_as_one_changed_line()
+    {
+        let buffer = Rc::new(RefCell::new(Vec::new()));
+        let mut presenter = JsonPresenter::new(Box::new(SharedBuffer(buffer.clone())), JsonMode::Document);
+        let mut sd = StringDiff::new();
+
+        presenter.header("hello!", "hola!").unwrap();
+        presenter.present("hello!", "hola!", None, None, &sd.get_diff("hello!", "hola!")).unwrap();
+        presenter.footer().unwrap();
+
+        let out = String::from_utf8(buffer.borrow().clone()).unwrap();
+        assert!(out.contains("\"old_start\":null,\"old_count\":0,\"new_start\":null,\"new_count\":0,"));
+        assert!(out.contains("\"stats\":{\"hunks\":1,\"lines_inserted\":0,\"lines_removed\":0,\"lines_changed\":1,"));
+    }
+
+    #[test]
+    fn newline_delimited()
+    {
+        let out = render(JsonMode::Lines);
+        let lines : Vec<&str> = out.lines().collect();
+
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], "{\"type\":\"header\",\"old\":\"a.txt\",\"new\":\"b\\\"c.txt\"}");
+        assert!(lines[1].starts_with("{\"type\":\"hunk\",\"old_start\":2,"));
+        assert!(lines[2].starts_with("{\"type\":\"hunk\",\"old_start\":4,"));
+        assert!(lines[3].starts_with("{\"type\":\"stats\",\"hunks\":2,"));
+    }
+}
@@ -0,0 +1,645 @@
+//! Line level diff
+//! Contains public structure LineDiff
+//! which computes edit script between two sequences of lines.
+//! Script is returned in forward order and covers both sequences completely,
+//! unchanged runs included, so it can be walked line by line.
+//! Example:
+//!
+//!   let mut ld = LineDiff::new();
+//!   let script = ld.get_diff(&old_lines, &new_lines);
+//!
+//! Large files are split into regions at anchor lines, and regions are compared in parallel:
+//!
+//!   let script = linediff::get_diff_parallel(&old_lines, &new_lines, 4, None);
+//!
+//! Comparison ending at a deadline gives valid, but not minimal script: lines not compared
+//! in time are removed and inserted whole.
+//!
+use std;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::time::Instant;
+use diff::difflib::DiffOperation;
+use diff::compaction;
+use diff::parallel;
+
+
+/// Sequences with more lines than this are split at anchor lines, see get_diff_parallel
+pub const SPLIT_LINES : usize = 4096;
+
+/// Minimal count of old lines of a region between anchors
+const REGION_LINES : usize = 1024;
+
+
+/// Block of consecutive lines affected by the same operation.
+/// old_start: Index of the first line of the block in the old sequence
+/// new_start: Index of the first line of the block in the new sequence
+/// count: Count of consecutive lines in the block
+/// operation: None for unchanged lines, Remove for lines present only in old
+///            and Insert for lines present only in new sequence
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LineCell
+{
+    pub old_start : usize,
+    pub new_start : usize,
+    pub count : usize,
+    pub operation : DiffOperation
+}
+
+
+impl LineCell
+{
+    /// True for the cell at the original place of moved block, false for its destination or not moved cell.
+    /// Destination cell never starts at the same old index: it either follows the source, 
+    /// or precedes it with the source removal emitted first within a change.
+    pub fn is_move_source(&self) -> bool
+    {
+        match self.operation {
+            DiffOperation::Moved { from, .. } => self.old_start == from,
+            _ => false,
+        }
+    }
+}
+
+
+/// Line difference of 2 named files, unit of storage and transfer of the result.
+/// old_name, new_name: Names of compared files 
+/// script: Forward ordered edit script, as returned by LineDiff::get_diff
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[allow(dead_code)]
+pub struct FileDiff
+{
+    pub old_name : String,
+    pub new_name : String,
+    pub script : Vec<LineCell>
+}
+
+
+pub struct LineDiff
+{
+    forward  : Vec<usize>, //furthest reaching paths of the forward search, by diagonal
+    backward : Vec<usize>, //the same for the backward search
+    deadline : Option<Instant>,
+}
+
+
+impl LineDiff
+{
+    pub fn new() -> LineDiff
+    {
+        LineDiff { forward : Vec::new(), backward : Vec::new(), deadline : None }
+    }
+
+
+    /// Sets time, after which the rest of compared lines is removed and inserted whole,
+    /// without searching for their common ones. None by default
+    pub fn set_deadline(&mut self, deadline : Option<Instant>)
+    {
+        self.deadline = deadline;
+    }
+
+
+    /// Returns forward ordered edit script between 2 sequences of lines provided as parameters.
+    /// Ambiguous inserted and removed blocks are slided to natural boundaries, see compaction module.
+    #[allow(dead_code)]
+    pub fn get_diff<T : AsRef<str>>(&mut self, _old : &[T], _new : &[T]) -> Vec<LineCell>
+    {
+        let mut script = self.edit_script(_old, _new);
+        compaction::compact(&mut script, _old, _new);
+
+        script
+    }
+
+
+    /// Computes raw, not compacted, edit script with minimal count of removed and inserted lines.
+    /// Linear space variant of Eugene W. Myers' O(ND) algorithm, paper source: http://www.xmailserver.org/diff2.pdf
+    /// Memory does not depend on the product of sequence lengths, only on their sum.
+    fn edit_script<T : AsRef<str>>(&mut self, _old : &[T], _new : &[T]) -> Vec<LineCell>
+    {
+        let size = 2 * max_d(_old.len(), _new.len());
+        self.forward.resize(size, 0);
+        self.backward.resize(size, 0);
+
+        let mut equal = Vec::new();
+        self.conquer(_old, 0 .. _old.len(), _new, 0 .. _new.len(), &mut equal);
+
+        script_of(&equal, _old.len(), _new.len())
+    }
+
+
+    /// Appends runs of equal lines (old start, new start, count) of the ranges in forward order.
+    /// Shared prefix and suffix are taken first, the rest is split at the middle snake.
+    fn conquer<T : AsRef<str>>(&mut self, _old : &[T], mut old_range : Range<usize>, _new : &[T], mut new_range : Range<usize>, equal : &mut Vec<(usize, usize, usize)>)
+    {
+        let prefix = common_prefix(&_old[old_range.clone()], &_new[new_range.clone()]);
+        if prefix > 0 {
+            equal.push((old_range.start, new_range.start, prefix));
+        }
+        old_range.start += prefix;
+        new_range.start += prefix;
+
+        let suffix = common_suffix(&_old[old_range.clone()], &_new[new_range.clone()]);
+        old_range.end -= suffix;
+        new_range.end -= suffix;
+
+        //nothing left at one side: the other one is removed or inserted whole, so are both after the deadline
+        if !old_range.is_empty() && !new_range.is_empty()
+        {
+            if let Some((x, y)) = self.middle_snake(_old, old_range.clone(), _new, new_range.clone())
+            {
+                self.conquer(_old, old_range.start .. x, _new, new_range.start .. y, equal);
+                self.conquer(_old, x .. old_range.end, _new, y .. new_range.end, equal);
+            }
+        }
+
+        if suffix > 0 {
+            equal.push((old_range.end, new_range.end, suffix));
+        }
+    }
+
+
+    /// Start of the middle snake of the shortest path between both ranges: the path is split there
+    /// into 2 paths, each of them at most half as long. Forward and backward searches extend
+    /// paths with 1, 2 .. D changes, until they overlap on a diagonal. None when the deadline passes first.
+    fn middle_snake<T : AsRef<str>>(&mut self, _old : &[T], old_range : Range<usize>, _new : &[T], new_range : Range<usize>) -> Option<(usize, usize)>
+    {
+        let (old_part, new_part) = (&_old[old_range.clone()], &_new[new_range.clone()]);
+        let (n, m) = (old_part.len() as isize, new_part.len() as isize);
+        let delta  = n - m;
+        let odd    = delta & 1 == 1;
+
+        //diagonal k = x - y is kept at index k + offset
+        let offset = max_d(_old.len(), _new.len()) as isize;
+        let at = |k : isize| (k + offset) as usize;
+        self.forward[at(1)]  = 0;
+        self.backward[at(1)] = 0;
+
+        for d in 0 .. max_d(old_part.len(), new_part.len()) as isize
+        {
+            if self.deadline.is_some_and(|t| Instant::now() >= t) {
+                return None;
+            }
+
+            for k in (-d ..= d).rev().step_by(2)
+            {
+                let mut x = if k == -d || (k != d && self.forward[at(k - 1)] < self.forward[at(k + 1)]) {
+                    self.forward[at(k + 1)] as isize
+                } else {
+                    self.forward[at(k - 1)] as isize + 1
+                };
+                let (x0, y0) = (x, x - k);
+                if x < n && y0 < m {
+                    x += common_prefix(&old_part[x as usize ..], &new_part[y0 as usize ..]) as isize;
+                }
+                self.forward[at(k)] = x as usize;
+
+                if odd && (k - delta).abs() < d && x + self.backward[at(delta - k)] as isize >= n {
+                    return Some((old_range.start + x0 as usize, new_range.start + y0 as usize));
+                }
+            }
+
+            //ascending diagonals: of equally long paths, the one removing lines first is taken
+            for k in (-d ..= d).step_by(2)
+            {
+                let mut x = if k == -d || (k != d && self.backward[at(k - 1)] < self.backward[at(k + 1)]) {
+                    self.backward[at(k + 1)] as isize
+                } else {
+                    self.backward[at(k - 1)] as isize + 1
+                };
+                let mut y = x - k;
+                if x < n && y < m
+                {
+                    let shared = common_suffix(&old_part[.. (n - x) as usize], &new_part[.. (m - y) as usize]) as isize;
+                    x += shared;
+                    y += shared;
+                }
+                self.backward[at(k)] = x as usize;
+
+                if !odd && (k - delta).abs() <= d && x + self.forward[at(delta - k)] as isize >= n {
+                    return Some((old_range.start + (n - x) as usize, new_range.start + (m - y) as usize));
+                }
+            }
+        }
+
+        //paths always meet within max_d steps
+        unreachable!("middle snake not found")
+    }
+}
+
+
+/// Same as LineDiff::get_diff, sequences longer than SPLIT_LINES are split at anchor lines into regions
+/// compared on `threads` threads, each with its own engine. Anchors are lines present once in each
+/// sequence, in the same order at both sides. Regions do not depend on count of threads, neither does the script,
+/// unless the deadline passes (see LineDiff::set_deadline)
+pub fn get_diff_parallel<T : AsRef<str> + Sync>(_old : &[T], _new : &[T], threads : usize, deadline : Option<Instant>) -> Vec<LineCell>
+{
+    let regions = regions(_old, _new);
+    let engine = || {
+        let mut ld = LineDiff::new();
+        ld.set_deadline(deadline);
+        ld
+    };
+    let scripts = parallel::map(&regions, threads, engine, |ld, (old_range, new_range)| {
+        let mut script = ld.edit_script(&_old[old_range.clone()], &_new[new_range.clone()]);
+        for cell in script.iter_mut()
+        {
+            cell.old_start += old_range.start;
+            cell.new_start += new_range.start;
+        }
+        script
+    });
+
+    let mut script : Vec<LineCell> = Vec::new();
+    for (i, cells) in scripts.into_iter().enumerate()
+    {
+        if i > 0 {
+            //anchor line between regions
+            let (old_range, new_range) = &regions[i - 1];
+            push_cell(&mut script, LineCell{old_start: old_range.end, new_start: new_range.end, count: 1, operation: DiffOperation::None});
+        }
+        for cell in cells {
+            push_cell(&mut script, cell);
+        }
+    }
+    compaction::compact(&mut script, _old, _new);
+
+    script
+}
+
+
+/// Appends the cell, unchanged runs are joined
+fn push_cell(script : &mut Vec<LineCell>, cell : LineCell)
+{
+    match script.last_mut()
+    {
+        Some(last) if last.operation == DiffOperation::None && cell.operation == DiffOperation::None => last.count += cell.count,
+        _ => script.push(cell),
+    }
+}
+
+
+/// Old and new ranges of regions, separated by single anchor line.
+/// Short sequences are a single region, regions of long ones have at least REGION_LINES old lines
+fn regions<T : AsRef<str>>(_old : &[T], _new : &[T]) -> Vec<(Range<usize>, Range<usize>)>
+{
+    let mut regions = Vec::new();
+    let (mut old_start, mut new_start) = (0, 0);
+    if std::cmp::max(_old.len(), _new.len()) > SPLIT_LINES
+    {
+        for (i, j) in anchors(_old, _new)
+        {
+            if i >= old_start + REGION_LINES && i + REGION_LINES <= _old.len()
+            {
+                regions.push((old_start .. i, new_start .. j));
+                old_start = i + 1;
+                new_start = j + 1;
+            }
+        }
+    }
+    regions.push((old_start .. _old.len(), new_start .. _new.len()));
+
+    regions
+}
+
+
+/// Lines present once in each sequence (old index, new index), the longest series of them
+/// in the same order at both sides, as patience diff takes them
+fn anchors<T : AsRef<str>>(_old : &[T], _new : &[T]) -> Vec<(usize, usize)>
+{
+    //count of occurrences and index of the line at both sides
+    let mut lines : HashMap<&str, (usize, usize, usize, usize)> = HashMap::new();
+    for (i, line) in _old.iter().enumerate()
+    {
+        let entry = lines.entry(line.as_ref()).or_insert((0, 0, 0, 0));
+        entry.0 += 1;
+        entry.2 = i;
+    }
+    for (j, line) in _new.iter().enumerate()
+    {
+        if let Some(entry) = lines.get_mut(line.as_ref()) {
+            entry.1 += 1;
+            entry.3 = j;
+        }
+    }
+
+    let mut unique : Vec<(usize, usize)> = lines.values().filter(|e| e.0 == 1 && e.1 == 1).map(|e| (e.2, e.3)).collect();
+    unique.sort_unstable();
+
+    //longest increasing subsequence of new indexes, by patience sorting
+    let mut tails : Vec<usize> = Vec::new();           //pile tops: pair ending the longest series of pile index + 1 pairs
+    let mut previous = vec![usize::MAX; unique.len()]; //pair before the pair in its series
+    for (k, pair) in unique.iter().enumerate()
+    {
+        let pile = tails.partition_point(|&t| unique[t].1 < pair.1);
+        if pile > 0 {
+            previous[k] = tails[pile - 1];
+        }
+        if pile == tails.len() {
+            tails.push(k);
+        }
+        else {
+            tails[pile] = k;
+        }
+    }
+
+    let mut result = Vec::with_capacity(tails.len());
+    let mut k = tails.last().cloned().unwrap_or(usize::MAX);
+    while k != usize::MAX
+    {
+        result.push(unique[k]);
+        k = previous[k];
+    }
+    result.reverse();
+
+    result
+}
+
+
+/// Count of steps after which forward and backward paths of sequences of given lengths surely meet
+fn max_d(old_len : usize, new_len : usize) -> usize
+{
+    (old_len + new_len).div_ceil(2) + 1
+}
+
+
+fn common_prefix<T : AsRef<str>>(_old : &[T], _new : &[T]) -> usize
+{
+    _old.iter().zip(_new).take_while(|(o, n)| o.as_ref() == n.as_ref()).count()
+}
+
+
+fn common_suffix<T : AsRef<str>>(_old : &[T], _new : &[T]) -> usize
+{
+    _old.iter().rev().zip(_new.iter().rev()).take_while(|(o, n)| o.as_ref() == n.as_ref()).count()
+}
+
+
+/// Forward ordered script of runs of equal lines: lines between them are changed,
+/// removed lines of a change are emitted before inserted ones
+fn script_of(equal : &[(usize, usize, usize)], old_len : usize, new_len : usize) -> Vec<LineCell>
+{
+    let mut v : Vec<LineCell> = Vec::new();
+    let (mut row, mut col) = (0, 0);
+    for &(old_start, new_start, count) in equal.iter().chain(std::iter::once(&(old_len, new_len, 0)))
+    {
+        if old_start > row {
+            v.push(LineCell{old_start: row, new_start: col, count: old_start - row, operation: DiffOperation::Remove});
+        }
+        if new_start > col {
+            v.push(LineCell{old_start, new_start: col, count: new_start - col, operation: DiffOperation::Insert});
+        }
+
+        //runs split by the recursion are joined
+        match v.last_mut()
+        {
+            Some(last) if last.operation == DiffOperation::None && old_start == row && new_start == col => last.count += count,
+            _ if count > 0 => v.push(LineCell{old_start, new_start, count, operation: DiffOperation::None}),
+            _ => {},
+        }
+        row = old_start + count;
+        col = new_start + count;
+    }
+
+    v
+}
+
+
+#[cfg(test)]
+mod tests
+{
+    use ::diff::linediff::*;
+
+    #[test]
+    fn equal_lines()
+    {
+        let _old = vec!["a", "b", "c"];
+        let _new = vec!["a", "b", "c"];
+        let mut ld = LineDiff::new();
+        let script = ld.get_diff(&_old, &_new);
+
+        assert_eq!(script.len(), 1);
+        assert_eq!(script[0], LineCell{old_start: 0, new_start: 0, count: 3, operation: DiffOperation::None});
+    }
+
+    #[test]
+    fn empty_sequences()
+    {
+        let _old : Vec<&str> = vec![];
+        let _new : Vec<&str> = vec![];
+        let mut ld = LineDiff::new();
+        let script = ld.get_diff(&_old, &_new);
+
+        assert_eq!(script.len(), 0);
+    }
+
+    #[test]
+    fn insert_line()
+    {
+        let _old = vec!["a", "c"];
+        let _new = vec!["a", "b", "c"];
+        let mut ld = LineDiff::new();
+        let script = ld.get_diff(&_old, &_new);
+
+        assert_eq!(script.len(), 3);
+        assert_eq!(script[1], LineCell{old_start: 1, new_start: 1, count: 1, operation: DiffOperation::Insert});
+    }
+
+    #[test]
+    fn replace_lines()
+    {
+        let _old = vec!["a", "x", "y", "d"];
+        let _new = vec!["a", "b", "d", "e"];
+        let mut ld = LineDiff::new();
+        let script = ld.get_diff(&_old, &_new);
+
+        assert_eq!(script.len(), 5);
+        assert_eq!(script[1], LineCell{old_start: 1, new_start: 1, count: 2, operation: DiffOperation::Remove});
+        assert_eq!(script[2], LineCell{old_start: 3, new_start: 1, count: 1, operation: DiffOperation::Insert});
+        assert_eq!(script[3], LineCell{old_start: 3, new_start: 2, count: 1, operation: DiffOperation::None});
+        assert_eq!(script[4], LineCell{old_start: 4, new_start: 3, count: 1, operation: DiffOperation::Insert});
+    }
+
+    #[test]
+    fn remove_all()
+    {
+        let _old = vec!["a", "b"];
+        let _new : Vec<&str> = vec![];
+        let mut ld = LineDiff::new();
+        let script = ld.get_diff(&_old, &_new);
+
+        assert_eq!(script, vec![LineCell{old_start: 0, new_start: 0, count: 2, operation: DiffOperation::Remove}]);
+    }
+
+    #[test]
+    fn large_files_with_few_changes()
+    {
+        let _old : Vec<String> = (0 .. 20000).map(|i| format!("line {}", i)).collect();
+        let mut _new = _old.clone();
+        _new[3] = String::from("changed");
+        _new.insert(19990, String::from("inserted"));
+
+        let mut ld = LineDiff::new();
+        let script = ld.get_diff(&_old, &_new);
+        assert_eq!(script, vec![
+            LineCell{old_start: 0, new_start: 0, count: 3, operation: DiffOperation::None},
+            LineCell{old_start: 3, new_start: 3, count: 1, operation: DiffOperation::Remove},
+            LineCell{old_start: 4, new_start: 3, count: 1, operation: DiffOperation::Insert},
+            LineCell{old_start: 4, new_start: 4, count: 19986, operation: DiffOperation::None},
+            LineCell{old_start: 19990, new_start: 19990, count: 1, operation: DiffOperation::Insert},
+            LineCell{old_start: 19990, new_start: 19991, count: 10, operation: DiffOperation::None},
+        ]);
+
+        //search buffers grow with the sum of lengths, not with the product
+        assert!(ld.forward.len() + ld.backward.len() <= 2 * (_old.len() + _new.len() + 4));
+    }
+
+    #[test]
+    fn minimal_script()
+    {
+        //count of changed lines is the same as by the longest common subsequence
+        fn property(_old : &[String], _new : &[String]) -> bool
+        {
+            let mut lcs = vec![vec![0; _new.len() + 1]; _old.len() + 1];
+            for i in 0 .. _old.len()
+            {
+                for j in 0 .. _new.len() {
+                    lcs[i + 1][j + 1] = if _old[i] == _new[j] { lcs[i][j] + 1 } else { lcs[i][j + 1].max(lcs[i + 1][j]) };
+                }
+            }
+
+            let script = LineDiff::new().get_diff(_old, _new);
+            let (mut row, mut col, mut changed) = (0, 0, 0);
+            for cell in script.iter()
+            {
+                if cell.old_start != row || cell.new_start != col {
+                    return false;
+                }
+                match cell.operation
+                {
+                    DiffOperation::None => {
+                        if _old[row .. row + cell.count] != _new[col .. col + cell.count] {
+                            return false;
+                        }
+                        row += cell.count;
+                        col += cell.count;
+                    },
+                    DiffOperation::Remove => row += cell.count,
+                    _ => col += cell.count,
+                }
+                if cell.operation != DiffOperation::None {
+                    changed += cell.count;
+                }
+            }
+
+            (row, col) == (_old.len(), _new.len()) && changed == _old.len() + _new.len() - 2 * lcs[_old.len()][_new.len()]
+        }
+
+        //every pair of sequences of up to 4 lines out of 3 distinct ones
+        let mut sequences : Vec<Vec<String>> = vec![vec![]];
+        let mut last = 0;
+        for _ in 0 .. 4
+        {
+            let first = last;
+            last = sequences.len();
+            for i in first .. last
+            {
+                for line in ["a", "b", "c"].iter()
+                {
+                    let mut longer = sequences[i].clone();
+                    longer.push(line.to_string());
+                    sequences.push(longer);
+                }
+            }
+        }
+
+        for _old in sequences.iter()
+        {
+            for _new in sequences.iter() {
+                assert!(property(_old, _new), "{:?} {:?}", _old, _new);
+            }
+        }
+    }
+
+    #[test]
+    fn parallel_regions()
+    {
+        let _old : Vec<String> = (0 .. 10000).map(|i| if i % 10 == 0 { String::from("}") } else { format!("line {}", i) }).collect();
+        let mut _new = _old.clone();
+        for i in [10, 2501, 5000, 9990].iter().rev() {
+            _new.insert(*i, String::from("}"));
+        }
+        _new[7777] = String::from("changed");
+        _new.remove(3333);
+
+        let regions = regions(&_old, &_new);
+        assert!(regions.len() > 2);
+        assert!(regions.iter().all(|(old_range, _)| old_range.len() >= REGION_LINES));
+        for k in 1 .. regions.len() {
+            assert_eq!(_old[regions[k - 1].0.end], _new[regions[k - 1].1.end]);
+        }
+
+        //anchors do not cut changes here, so the script is the same as of the whole files
+        let script = get_diff_parallel(&_old, &_new, 1, None);
+        assert_eq!(script, LineDiff::new().get_diff(&_old, &_new));
+        assert_eq!(get_diff_parallel(&_old, &_new, 4, None), script);
+
+        let short = get_diff_parallel(&_old[.. 100], &_new[.. 100], 4, None);
+        assert_eq!(short, LineDiff::new().get_diff(&_old[.. 100], &_new[.. 100]));
+    }
+
+    #[test]
+    fn anchor_order()
+    {
+        //"a" moved after "c" breaks the order, "x" is not unique, "y" is only in old lines
+        let _old = vec!["a", "b", "x", "c", "x", "d", "y", "e"];
+        let _new = vec!["b", "x", "c", "a", "d", "e"];
+        assert_eq!(anchors(&_old, &_new), vec![(1, 0), (3, 2), (5, 4), (7, 5)]);
+    }
+
+    #[test]
+    fn passed_deadline()
+    {
+        use std::time::Instant;
+
+        let _old = vec!["a", "1", "2", "3", "b", "e"];
+        let _new = vec!["c", "1", "2", "3", "d", "e"];
+        let mut ld = LineDiff::new();
+        assert_eq!(ld.get_diff(&_old, &_new).len(), 6);
+
+        //the common suffix is still found, lines before it are not compared
+        ld.set_deadline(Some(Instant::now()));
+        let script = ld.get_diff(&_old, &_new);
+        assert_eq!(script, vec![LineCell{old_start: 0, new_start: 0, count: 5, operation: DiffOperation::Remove},
+                                LineCell{old_start: 5, new_start: 0, count: 5, operation: DiffOperation::Insert},
+                                LineCell{old_start: 5, new_start: 5, count: 1, operation: DiffOperation::None}]);
+        assert_eq!(get_diff_parallel(&_old, &_new, 2, Some(Instant::now())), script);
+
+        ld.set_deadline(None);
+        assert_eq!(ld.get_diff(&_old, &_new).len(), 6);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip()
+    {
+        extern crate serde_json;
+
+        let _old = vec!["a", "x", "y", "d"];
+        let _new = vec!["a", "b", "d", "e"];
+        let mut ld = LineDiff::new();
+        let file_diff = FileDiff { old_name : String::from("a.txt"), new_name : String::from("b.txt"), script : ld.get_diff(&_old, &_new) };
+
+        let json = serde_json::to_string(&file_diff).unwrap();
+        let back : FileDiff = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, file_diff);
+
+        //wire representation is stable
+        assert_eq!(serde_json::to_string(&file_diff.script[1]).unwrap(), "{\"old_start\":1,\"new_start\":1,\"count\":2,\"operation\":\"remove\"}");
+    }
+}
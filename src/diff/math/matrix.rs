@@ -1,74 +1,315 @@
-    use std::ops::{Index,IndexMut};
+//! Generic 2D grid stored row by row in single vector
+//! Cells are indexed by (row, column), both are checked against current dimensions.
+//! Allocation is kept when the grid shrinks, so the same grid can be reused for many computations:
+//!
+//!   let mut mtx : Matrix<u32> = Matrix::new(3, 4);
+//!   mtx[(2, 3)] = 7;
+//!   mtx.resize(4, 4, 0);           //contents are kept
+//!   let row : &[u32] = mtx.row(2);
+//!   println!("{}", mtx);
+//!
+use std::fmt;
+use std::ops::{Index,IndexMut};
+use std::slice::{Chunks,ChunksMut,Iter};
 
 
-    ///Matrix for tracing difference during single comparison
-    pub struct Matrix<T>       
+///Grid of `rows` x `cols` cells
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+pub struct Matrix<T>
+{
+    rows : usize,
+    cols : usize,
+    data : Vec<T>, //cells of the grid, followed by spare ones kept from bigger dimensions
+}
+
+
+impl<T> Matrix<T>
+{
+    /// Grid with every cell set to the default value
+    pub fn new(rows : usize, cols : usize) -> Matrix<T>
+        where T : Default
+    {
+        let mut data = Vec::with_capacity(rows * cols);
+        data.resize_with(rows * cols, T::default);
+        Matrix { rows, cols, data }
+    }
+
+    /// Grid with every cell set to `val`
+    #[allow(dead_code)]
+    pub fn from_elem(rows : usize, cols : usize, val : T) -> Matrix<T>
+        where T : Clone
+    {
+        Matrix { rows, cols, data : vec![val; rows * cols] }
+    }
+
+    #[inline]
+    pub fn rows(&self) -> usize
+    {
+        self.rows
+    }
+
+    #[inline]
+    pub fn cols(&self) -> usize
+    {
+        self.cols
+    }
+
+    /// Count of allocated cells, at least rows * cols
+    pub fn capacity(&self) -> usize
+    {
+        self.data.len()
+    }
+
+    #[allow(dead_code)]
+    pub fn get(&self, row : usize, col : usize) -> Option<&T>
+    {
+        if row < self.rows && col < self.cols { Some(&self.data[row * self.cols + col]) } else { None }
+    }
+
+    #[allow(dead_code)]
+    pub fn get_mut(&mut self, row : usize, col : usize) -> Option<&mut T>
+    {
+        if row < self.rows && col < self.cols { Some(&mut self.data[row * self.cols + col]) } else { None }
+    }
+
+    /// Cells of the row
+    #[allow(dead_code)]
+    pub fn row(&self, row : usize) -> &[T]
+    {
+        assert!(row < self.rows, "row {} out of {}x{} matrix", row, self.rows, self.cols);
+        &self.data[row * self.cols .. (row + 1) * self.cols]
+    }
+
+    #[allow(dead_code)]
+    pub fn row_mut(&mut self, row : usize) -> &mut [T]
+    {
+        assert!(row < self.rows, "row {} out of {}x{} matrix", row, self.rows, self.cols);
+        &mut self.data[row * self.cols .. (row + 1) * self.cols]
+    }
+
+    /// Rows from the first one, as slices
+    #[allow(dead_code)]
+    pub fn iter_rows(&self) -> Chunks<'_, T>
     {
-        pub r_cnt     : usize,
-        pub c_cnt     : usize,     
-        pub r_tot_cnt : usize, 
-        pub c_tot_cnt : usize,  
-        data     : Vec<T>
+        self.data[.. self.rows * self.cols].chunks(self.cols.max(1))
     }
 
-   
+    #[allow(dead_code)]
+    pub fn iter_rows_mut(&mut self) -> ChunksMut<'_, T>
+    {
+        let cols = self.cols.max(1);
+        self.data[.. self.rows * self.cols].chunks_mut(cols)
+    }
 
-    impl<T> Matrix<T>         
+    /// Cells row by row
+    pub fn iter(&self) -> Iter<'_, T>
     {
+        self.data[.. self.rows * self.cols].iter()
+    }
 
-        pub fn new(row_count : usize, col_count: usize) -> Matrix<T>
-            where T : Default
-        {           
-           let mut _m = Matrix::<T> {  
-                r_cnt       : row_count,  
-                c_cnt       : col_count, 
-                r_tot_cnt   : row_count,
-                c_tot_cnt   : col_count,
-                data        : Vec::new(),          
-            };          
+    #[allow(dead_code)]
+    pub fn fill(&mut self, val : T)
+        where T : Clone
+    {
+        let size = self.rows * self.cols;
+        self.data[.. size].fill(val);
+    }
 
-            //init with default value
-            _m.data = Vec::with_capacity(row_count * col_count);
-            for _ in 0.._m.data.capacity() {
-                _m.data.push(Default::default());
+    /// Changes dimensions, cells keep their (row, column) and new ones are set to `val`
+    #[allow(dead_code)]
+    pub fn resize(&mut self, rows : usize, cols : usize, val : T)
+        where T : Clone
+    {
+        let mut data = Vec::with_capacity(rows * cols);
+        for r in 0 .. rows
+        {
+            if r < self.rows
+            {
+                let kept = cols.min(self.cols);
+                data.extend_from_slice(&self.data[r * self.cols .. r * self.cols + kept]);
+                data.resize(data.len() + cols - kept, val.clone());
+            }
+            else {
+                data.resize(data.len() + cols, val.clone());
             }
-           _m
         }
-      
-        #[inline]
-        pub fn set_size(&mut self,  rcnt : usize,  ccnt : usize)
+
+        self.rows = rows;
+        self.cols = cols;
+        self.data = data;
+    }
+
+    /// Changes dimensions and sets every cell to `val`
+    #[allow(dead_code)]
+    pub fn reset(&mut self, rows : usize, cols : usize, val : T)
+        where T : Clone
+    {
+        if rows * cols > self.data.len() {
+            self.data.resize(rows * cols, val.clone());
+        }
+        self.rows = rows;
+        self.cols = cols;
+        self.fill(val);
+    }
+
+    /// Changes dimensions without touching cells, allocation is only grown.
+    /// Values of cells are unspecified afterwards: for buffers overwritten by every computation
+    pub fn reshape(&mut self, rows : usize, cols : usize)
+        where T : Default
+    {
+        if rows * cols > self.data.len() {
+            self.data.resize_with(rows * cols, T::default);
+        }
+        self.rows = rows;
+        self.cols = cols;
+    }
+
+    /// Releases cells and spare allocation
+    pub fn clear(&mut self)
+    {
+        self.rows = 0;
+        self.cols = 0;
+        self.data = Vec::new();
+    }
+
+    #[inline]
+    fn check(&self, row : usize, col : usize)
+    {
+        assert!(row < self.rows && col < self.cols, "cell ({}, {}) out of {}x{} matrix", row, col, self.rows, self.cols);
+    }
+}
+
+
+impl<T> Index<(usize, usize)> for Matrix<T>
+{
+    type Output = T;
+
+    #[inline]
+    fn index(&self, (row, col) : (usize, usize)) -> &T
+    {
+        self.check(row, col);
+        &self.data[row * self.cols + col]
+    }
+}
+
+impl<T> IndexMut<(usize, usize)> for Matrix<T>
+{
+    #[inline]
+    fn index_mut(&mut self, (row, col) : (usize, usize)) -> &mut T
+    {
+        self.check(row, col);
+        &mut self.data[row * self.cols + col]
+    }
+}
+
+
+///Rows on separate lines, cells aligned to the widest one
+impl<T : fmt::Display> fmt::Display for Matrix<T>
+{
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result
+    {
+        let cells : Vec<String> = self.iter().map(|c| c.to_string()).collect();
+        let width = cells.iter().map(|c| c.chars().count()).max().unwrap_or(0);
+
+        for row in cells.chunks(self.cols.max(1))
         {
-            self.r_cnt = rcnt;
-            self.c_cnt = ccnt;
-        }      
+            let line : Vec<String> = row.iter().map(|c| format!("{:>width$}", c, width = width)).collect();
+            writeln!(f, "{}", line.join(" "))?;
+        }
+
+        Ok(())
+    }
+}
 
-        pub fn fill_with_val(&mut self, val : T)
-                where T: Clone
+
+#[cfg(test)]
+mod tests
+{
+    use ::diff::math::Matrix;
+
+    fn numbered(rows : usize, cols : usize) -> Matrix<usize>
+    {
+        let mut mtx = Matrix::new(rows, cols);
+        for r in 0 .. rows
         {
-            let size = self.r_cnt * self.c_cnt;
-            for i in 0..size {
-                self.data[i] = val.clone();
+            for c in 0 .. cols {
+                mtx[(r, c)] = r * 10 + c;
             }
         }
+        mtx
     }
 
-     impl<T> Index<(usize, usize)> for Matrix<T>        
-     {          
-           type Output = T;
-           
-            fn index(&self, (i, j): (usize, usize)) -> &T 
-            {
-                assert!(i < self.r_cnt * self.c_cnt && j < self.c_cnt);               
-                &self.data[i * self.c_cnt + j]
-            }
-     } 
-
-     impl<T> IndexMut<(usize, usize)> for Matrix<T>         
-     {      
-        fn index_mut(&mut self, (i, j): (usize, usize)) -> &mut T 
-        {            
-            assert!(i < self.r_cnt * self.c_cnt && j < self.c_cnt);          
-            &mut self.data[i * self.c_cnt + j]         
+    #[test]
+    fn bounds()
+    {
+        let mtx = numbered(2, 3);
+        assert_eq!(mtx[(1, 2)], 12);
+        assert_eq!(mtx.get(1, 2), Some(&12));
+        assert_eq!(mtx.get(2, 0), None);
+        assert_eq!(mtx.get(0, 3), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "out of 2x3 matrix")]
+    fn row_out_of_range()
+    {
+        //(0, 4) is within the data, but not within the row
+        let mtx = numbered(2, 3);
+        let _ = mtx[(0, 4)];
+    }
+
+    #[test]
+    fn rows_and_iterators()
+    {
+        let mut mtx = numbered(3, 2);
+        assert_eq!(mtx.row(1), &[10, 11]);
+        mtx.row_mut(2)[0] = 99;
+
+        let rows : Vec<&[usize]> = mtx.iter_rows().collect();
+        assert_eq!(rows, vec![&[0, 1][..], &[10, 11][..], &[99, 21][..]]);
+        assert_eq!(mtx.iter().sum::<usize>(), 142);
+
+        for row in mtx.iter_rows_mut() {
+            row[1] = 0;
         }
+        assert_eq!(mtx.iter().filter(|&&c| c == 0).count(), 4);
+    }
+
+    #[test]
+    fn resize_keeps_cells()
+    {
+        let mut mtx = numbered(2, 3);
+        mtx.resize(3, 2, 7);
+        assert_eq!(mtx.iter_rows().collect::<Vec<_>>(), vec![&[0, 1][..], &[10, 11][..], &[7, 7][..]]);
+
+        mtx.resize(2, 4, 5);
+        assert_eq!(mtx.iter_rows().collect::<Vec<_>>(), vec![&[0, 1, 5, 5][..], &[10, 11, 5, 5][..]]);
+
+        mtx.reset(1, 3, 2);
+        assert_eq!(mtx.row(0), &[2, 2, 2]);
+    }
+
+    #[test]
+    fn reshape_keeps_allocation()
+    {
+        let mut mtx : Matrix<u8> = Matrix::new(10, 10);
+        mtx.reshape(2, 5);
+        assert_eq!((mtx.rows(), mtx.cols(), mtx.capacity()), (2, 5, 100));
+
+        mtx.reshape(20, 10);
+        assert_eq!(mtx.capacity(), 200);
+
+        mtx.clear();
+        assert_eq!((mtx.rows(), mtx.capacity()), (0, 0));
+    }
+
+    #[test]
+    fn display()
+    {
+        let mtx = numbered(2, 2);
+        assert_eq!(format!("{}", mtx), " 0  1\n10 11\n");
+        assert_eq!(format!("{}", Matrix::<u32>::new(0, 0)), "");
     }
- 
+}
@@ -1,2 +1,4 @@
 pub mod matrix;
-pub use self::matrix::Matrix;
\ No newline at end of file
+pub mod packed;
+pub use self::matrix::Matrix;
+pub use self::packed::PackedMatrix;
@@ -0,0 +1,156 @@
+//! Grid of 2 bit values, 4 cells packed in every byte
+//! Used where a cell only holds one of few states, e.g. direction of the traceback of diff,
+//! so big grids take a quarter of a byte per cell. Like Matrix, the allocation is only grown:
+//!
+//!   let mut dirs = PackedMatrix::new(3, 4);
+//!   dirs.set(2, 3, 1);
+//!   assert_eq!(dirs.get(2, 3), 1);
+//!   dirs.reshape(100, 100);        //contents are unspecified
+//!
+
+
+/// Cells in one byte
+const CELLS_PER_BYTE : usize = 4;
+
+
+///Grid of `rows` x `cols` values in range 0 ..= 3
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct PackedMatrix
+{
+    rows : usize,
+    cols : usize,
+    data : Vec<u8>,
+}
+
+
+impl PackedMatrix
+{
+    /// Grid with every cell set to 0
+    pub fn new(rows : usize, cols : usize) -> PackedMatrix
+    {
+        PackedMatrix { rows, cols, data : vec![0; bytes_for(rows * cols)] }
+    }
+
+    #[cfg(test)]
+    pub fn rows(&self) -> usize
+    {
+        self.rows
+    }
+
+    #[cfg(test)]
+    pub fn cols(&self) -> usize
+    {
+        self.cols
+    }
+
+    /// Count of allocated bytes, at least rows * cols / 4
+    pub fn capacity(&self) -> usize
+    {
+        self.data.len()
+    }
+
+    #[inline]
+    pub fn get(&self, row : usize, col : usize) -> u8
+    {
+        let (byte, shift) = self.position(row, col);
+        (self.data[byte] >> shift) & 0b11
+    }
+
+    #[inline]
+    pub fn set(&mut self, row : usize, col : usize, val : u8)
+    {
+        assert!(val <= 0b11, "value {} does not fit into 2 bits", val);
+        let (byte, shift) = self.position(row, col);
+        self.data[byte] = (self.data[byte] & !(0b11 << shift)) | (val << shift);
+    }
+
+    /// Changes dimensions without touching cells, allocation is only grown.
+    /// Values of cells are unspecified afterwards: for buffers overwritten by every computation
+    pub fn reshape(&mut self, rows : usize, cols : usize)
+    {
+        let size = bytes_for(rows * cols);
+        if size > self.data.len() {
+            self.data.resize(size, 0);
+        }
+        self.rows = rows;
+        self.cols = cols;
+    }
+
+    /// Releases cells and spare allocation
+    pub fn clear(&mut self)
+    {
+        self.rows = 0;
+        self.cols = 0;
+        self.data = Vec::new();
+    }
+
+    ///Byte of the cell and shift of its bits in the byte
+    #[inline]
+    fn position(&self, row : usize, col : usize) -> (usize, usize)
+    {
+        assert!(row < self.rows && col < self.cols, "cell ({}, {}) out of {}x{} matrix", row, col, self.rows, self.cols);
+        let index = row * self.cols + col;
+        (index / CELLS_PER_BYTE, (index % CELLS_PER_BYTE) * 2)
+    }
+}
+
+
+fn bytes_for(cells : usize) -> usize
+{
+    cells.div_ceil(CELLS_PER_BYTE)
+}
+
+
+#[cfg(test)]
+mod tests
+{
+    use ::diff::math::PackedMatrix;
+
+    #[test]
+    fn cells_do_not_overlap()
+    {
+        let mut mtx = PackedMatrix::new(3, 5);
+        assert_eq!(mtx.capacity(), 4);
+
+        for r in 0 .. 3
+        {
+            for c in 0 .. 5 {
+                mtx.set(r, c, ((r + c) % 4) as u8);
+            }
+        }
+        mtx.set(1, 1, 0);
+        mtx.set(1, 1, 3);
+
+        for r in 0 .. 3
+        {
+            for c in 0 .. 5
+            {
+                let expected = if (r, c) == (1, 1) { 3 } else { ((r + c) % 4) as u8 };
+                assert_eq!(mtx.get(r, c), expected);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "out of 3x5 matrix")]
+    fn row_out_of_range()
+    {
+        let mtx = PackedMatrix::new(3, 5);
+        mtx.get(0, 5);
+    }
+
+    #[test]
+    fn reshape_keeps_allocation()
+    {
+        let mut mtx = PackedMatrix::new(10, 10);
+        mtx.reshape(2, 5);
+        assert_eq!((mtx.rows(), mtx.cols(), mtx.capacity()), (2, 5, 25));
+
+        mtx.reshape(20, 10);
+        assert_eq!(mtx.capacity(), 50);
+
+        mtx.clear();
+        assert_eq!((mtx.rows(), mtx.capacity()), (0, 0));
+    }
+}
@@ -1,3 +1,21 @@
+//! Library part of the crate: public items used only by library users, not by the command line,
+//! are allowed to be dead one by one
+
 pub mod difflib;
+pub mod linediff;
+pub mod compaction;
+pub mod moves;
 pub mod presenter;
-mod math;
\ No newline at end of file
+pub mod html;
+pub mod json;
+pub mod stat;
+pub mod brief;
+pub mod hunk;
+pub mod unified;
+pub mod context;
+pub mod ed;
+pub mod patch;
+pub mod apply;
+pub mod parallel;
+pub mod bitparallel;
+pub mod math;
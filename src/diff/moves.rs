@@ -0,0 +1,194 @@
+//! Detection of moved blocks of lines
+//! When a function or paragraph is moved elsewhere, line edit script reports
+//! its removal plus an unrelated insertion. Detector pairs removed and inserted
+//! blocks with identical, or near-identical, content and marks both of them as
+//! DiffOperation::Moved, so presenters can show them with distinct markers.
+//! Example:
+//!
+//!   let mut script = ld.get_diff(&old_lines, &new_lines);
+//!   moves::detect_moves(&mut script, &old_lines, &new_lines);
+//!
+use diff::linediff::LineCell;
+use diff::difflib::{DiffOperation,StringDiff};
+
+
+const MIN_MOVED_CHARS : usize = 20;  //blocks with less non whitespace characters are never reported as moved
+const MIN_MOVED_RATIO : f64   = 0.8; //minimal average similarity of lines of near-identical blocks
+
+
+/// Marks removed and inserted blocks of the script which content matches as moved.
+/// Blocks are compared without blank lines on their edges, those stay removed or inserted.
+/// Both cells of the pair get DiffOperation::Moved with index of the block in old sequence
+/// (`from`) and in new sequence (`to`). Identical blocks are preferred over near-identical ones,
+/// removed block directly followed by inserted one is an edit in place and is never paired.
+pub fn detect_moves<T : AsRef<str>>(script : &mut Vec<LineCell>, _old : &[T], _new : &[T])
+{
+    let mut sd = StringDiff::new();
+
+    //moved part (offset and count) of the cell and its new operation
+    let mut moved : Vec<Option<(usize, usize, DiffOperation)>> = vec![None; script.len()];
+
+    for r in 0 .. script.len()
+    {
+        if script[r].operation != DiffOperation::Remove {
+            continue;
+        }
+
+        let (r_offset, r_count) = core(_old, script[r].old_start, script[r].count);
+        let removed = &_old[script[r].old_start + r_offset .. script[r].old_start + r_offset + r_count];
+        let weight : usize = removed.iter().map(|l| l.as_ref().chars().filter(|ch| !ch.is_whitespace()).count()).sum();
+        if weight < MIN_MOVED_CHARS {
+            continue;
+        }
+
+        let mut best : Option<(usize, usize, f64)> = None;
+        for (i, cell) in script.iter().enumerate()
+        {
+            if cell.operation != DiffOperation::Insert || moved[i].is_some() || i == r + 1 {
+                continue;
+            }
+
+            let (i_offset, i_count) = core(_new, cell.new_start, cell.count);
+            if i_count != r_count {
+                continue;
+            }
+
+            let inserted = &_new[cell.new_start + i_offset .. cell.new_start + i_offset + i_count];
+            let score = block_ratio(&mut sd, removed, inserted);
+            if score >= MIN_MOVED_RATIO && best.is_none_or(|(_, _, s)| score > s) {
+                best = Some((i, i_offset, score));
+            }
+        }
+
+        if let Some((i, i_offset, _)) = best
+        {
+            let op = DiffOperation::Moved { from : script[r].old_start + r_offset, to : script[i].new_start + i_offset };
+            moved[r] = Some((r_offset, r_count, op.clone()));
+            moved[i] = Some((i_offset, r_count, op));
+        }
+    }
+
+    //split moved cells, blank lines on the edges keep their operation
+    let mut result = Vec::with_capacity(script.len());
+    for (cell, m) in script.drain(..).zip(moved)
+    {
+        match m
+        {
+            Some((offset, count, op)) => {
+                if offset > 0 {
+                    result.push(sub_cell(&cell, 0, offset, cell.operation.clone()));
+                }
+                result.push(sub_cell(&cell, offset, count, op));
+                if offset + count < cell.count {
+                    result.push(sub_cell(&cell, offset + count, cell.count - offset - count, cell.operation.clone()));
+                }
+            },
+            None => result.push(cell),
+        }
+    }
+
+    *script = result;
+}
+
+
+///Returns offset and count of lines of the block without blank lines on its edges
+fn core<T : AsRef<str>>(lines : &[T], start : usize, count : usize) -> (usize, usize)
+{
+    let block   = &lines[start .. start + count];
+    let leading = block.iter().take_while(|l| l.as_ref().trim().is_empty()).count();
+    let trailing = block[leading ..].iter().rev().take_while(|l| l.as_ref().trim().is_empty()).count();
+
+    (leading, count - leading - trailing)
+}
+
+
+///Part of removed or inserted cell, starting at `offset` line of it
+fn sub_cell(cell : &LineCell, offset : usize, count : usize, operation : DiffOperation) -> LineCell
+{
+    match cell.operation
+    {
+        DiffOperation::Remove => LineCell{old_start: cell.old_start + offset, new_start: cell.new_start, count, operation},
+        _ => LineCell{old_start: cell.old_start, new_start: cell.new_start + offset, count, operation},
+    }
+}
+
+
+///Average similarity of corresponding lines of 2 blocks of the same size
+fn block_ratio<T : AsRef<str>>(sd : &mut StringDiff, removed : &[T], inserted : &[T]) -> f64
+{
+    let total : f64 = removed.iter().zip(inserted.iter())
+                             .map(|(l1, l2)| sd.ratio(l1.as_ref(), l2.as_ref()))
+                             .sum();
+
+    total / removed.len() as f64
+}
+
+
+#[cfg(test)]
+mod tests
+{
+    use ::diff::linediff::*;
+    use ::diff::moves::*;
+
+    #[test]
+    fn moved_function()
+    {
+        let _old = vec!["fn a() {", "    first_call();", "}", "", "fn b() {", "    second_call();", "}"];
+        let _new = vec!["fn b() {", "    second_call();", "}", "", "fn a() {", "    first_call();", "}"];
+        let mut ld = LineDiff::new();
+        let mut script = ld.get_diff(&_old, &_new);
+        detect_moves(&mut script, &_old, &_new);
+
+        let moved : Vec<&LineCell> = script.iter().filter(|c| c.operation == DiffOperation::Moved { from : 0, to : 4 }).collect();
+        assert_eq!(moved.len(), 2);
+        assert_eq!(*moved[0], LineCell{old_start: 0, new_start: 0, count: 3, operation: DiffOperation::Moved { from : 0, to : 4 }});
+        assert_eq!(*moved[1], LineCell{old_start: 7, new_start: 4, count: 3, operation: DiffOperation::Moved { from : 0, to : 4 }});
+        assert!(moved[0].is_move_source());
+        assert!(!moved[1].is_move_source());
+
+        //blank separator line stays removed and inserted
+        let blank : Vec<&LineCell> = script.iter().filter(|c| c.operation == DiffOperation::Remove || c.operation == DiffOperation::Insert).collect();
+        assert_eq!(blank.len(), 2);
+        assert_eq!(blank[0].count, 1);
+        assert_eq!(blank[1].count, 1);
+    }
+
+    #[test]
+    fn moved_and_edited_block()
+    {
+        let _old = vec!["let total = first_value + second_value;", "a", "b", "c"];
+        let _new = vec!["a", "b", "c", "let total = first_value + third_value;"];
+        let mut ld = LineDiff::new();
+        let mut script = ld.get_diff(&_old, &_new);
+        detect_moves(&mut script, &_old, &_new);
+
+        assert_eq!(script[0], LineCell{old_start: 0, new_start: 0, count: 1, operation: DiffOperation::Moved { from : 0, to : 3 }});
+        assert_eq!(script[2], LineCell{old_start: 4, new_start: 3, count: 1, operation: DiffOperation::Moved { from : 0, to : 3 }});
+    }
+
+    #[test]
+    fn edit_in_place_is_not_moved()
+    {
+        let _old = vec!["a", "let total = first + second;", "b"];
+        let _new = vec!["a", "let total = first + second + third;", "b"];
+        let mut ld = LineDiff::new();
+        let mut script = ld.get_diff(&_old, &_new);
+        detect_moves(&mut script, &_old, &_new);
+
+        assert_eq!(script[1].operation, DiffOperation::Remove);
+        assert_eq!(script[2].operation, DiffOperation::Insert);
+    }
+
+    #[test]
+    fn short_block_is_not_moved()
+    {
+        let _old = vec!["}", "a", "b"];
+        let _new = vec!["a", "b", "}"];
+        let mut ld = LineDiff::new();
+        let mut script = ld.get_diff(&_old, &_new);
+        detect_moves(&mut script, &_old, &_new);
+
+        assert_eq!(script[0].operation, DiffOperation::Remove);
+        assert_eq!(script[2].operation, DiffOperation::Insert);
+    }
+}
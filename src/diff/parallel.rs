@@ -0,0 +1,90 @@
+//! Parallel execution of independent jobs
+//! Jobs are taken by worker threads one by one. Every worker owns its own engine,
+//! created once per thread, e.g. StringDiff which keeps its matrices between comparisons.
+//! Results are returned in the order of jobs, so the output does not depend on scheduling.
+//! With single thread jobs run in the calling thread. Example:
+//!
+//!   let diffs = parallel::map(&pairs, 4, StringDiff::new, |sd, pair| sd.get_diff(pair.0, pair.1));
+//!
+use std;
+use std::cmp::min;
+use std::sync::atomic::{AtomicUsize,Ordering};
+use std::thread;
+
+
+/// Count of threads the system can run in parallel, 1 when it is unknown
+pub fn available_threads() -> usize
+{
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+
+/// Runs `job` for every item on `threads` workers, each of them with engine created by `init`.
+/// Results are in the order of items
+pub fn map<T, R, S, I, F>(items : &[T], threads : usize, init : I, job : F) -> Vec<R>
+    where T : Sync, R : Send, I : Fn() -> S + Sync, F : Fn(&mut S, &T) -> R + Sync
+{
+    let threads = min(threads, items.len());
+    if threads <= 1
+    {
+        let mut engine = init();
+        return items.iter().map(|item| job(&mut engine, item)).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let (init, job, next) = (&init, &job, &next);
+
+    let mut done : Vec<(usize, R)> = thread::scope(|scope| {
+        let workers : Vec<_> = (0 .. threads).map(|_| scope.spawn(move || {
+            let mut engine = init();
+            let mut results = Vec::new();
+            loop
+            {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                if i >= items.len() {
+                    break;
+                }
+                results.push((i, job(&mut engine, &items[i])));
+            }
+            results
+        })).collect();
+
+        workers.into_iter()
+               .flat_map(|w| w.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+               .collect()
+    });
+
+    done.sort_by_key(|(i, _)| *i);
+    done.into_iter().map(|(_, r)| r).collect()
+}
+
+
+#[cfg(test)]
+mod tests
+{
+    use ::diff::parallel::*;
+    use ::diff::difflib::StringDiff;
+
+    #[test]
+    fn keeps_order()
+    {
+        let items : Vec<usize> = (0 .. 1000).collect();
+        let expected : Vec<usize> = items.iter().map(|i| i * 2).collect();
+
+        for threads in [1, 2, 7].iter()
+        {
+            assert_eq!(map(&items, *threads, || 0, |_, i| i * 2), expected);
+        }
+        assert!(map(&[] as &[usize], 4, || 0, |_, i| *i).is_empty());
+    }
+
+    #[test]
+    fn engine_per_worker()
+    {
+        let pairs : Vec<(String, String)> = (0 .. 200).map(|i| (format!("line {}", i), format!("line {}!", i * 3))).collect();
+        let mut sd = StringDiff::new();
+        let expected : Vec<_> = pairs.iter().map(|p| sd.get_diff(&p.0, &p.1)).collect();
+
+        assert_eq!(map(&pairs, 4, StringDiff::new, |sd, p| sd.get_diff(&p.0, &p.1)), expected);
+    }
+}
@@ -0,0 +1,704 @@
+//! Patch parser
+//! Reads unified and context diffs, as `diff -u`, `diff -c` and `git diff` write them,
+//! back into hunks. Patch may contain several files, text before and between
+//! file headers (commit message, "Only in" lines) is ignored. Git extended headers
+//! give renames, copies, mode changes, created and deleted files. Example:
+//!
+//!   let patches = patch::parse(&text)?;
+//!   for p in &patches {
+//!       patch::present(p, &mut presenter)?;
+//!   }
+//!
+//! File names are kept as they are written in the patch, prefixes like "a/" and "b/"
+//! included, only timestamps are removed. Names of git rename and copy headers have no prefixes.
+//!
+//! Line terminators are normalised the way files are read for comparison: "\r\n" ends a line
+//! as "\n" does and carriage return is not part of the line. Patch changing only terminators
+//! of lines has equal removed and inserted lines, applier keeps the terminators of the patched file.
+use std;
+use std::fmt;
+
+use diff::difflib::StringDiff;
+use diff::hunk::{Hunk,LineKind,ScriptLine};
+use diff::presenter::Presenter;
+
+
+const DEV_NULL : &str = "/dev/null"; //name of missing side of created or deleted file
+const CONTEXT_SEPARATOR : &str = "***************";
+
+
+/// Changes of single file
+/// old_name, new_name: None for created (old) or deleted (new) file
+/// old_mode, new_mode: File modes of git headers
+/// rename, copy: New file is renamed or copied old one
+/// binary: Binary file, its changes are not parsed
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+#[derive(Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FilePatch
+{
+    pub old_name : Option<String>,
+    pub new_name : Option<String>,
+    pub old_mode : Option<u32>,
+    pub new_mode : Option<u32>,
+    pub rename   : bool,
+    pub copy     : bool,
+    pub binary   : bool,
+    pub hunks    : Vec<Hunk>,
+}
+
+impl FilePatch
+{
+    /// Patch changing new file back into old one
+    pub fn inverted(&self) -> FilePatch
+    {
+        FilePatch {
+            old_name : self.new_name.clone(),
+            new_name : self.old_name.clone(),
+            old_mode : self.new_mode,
+            new_mode : self.old_mode,
+            rename   : self.rename,
+            copy     : self.copy,
+            binary   : self.binary,
+            hunks    : self.hunks.iter().map(|h| h.inverted()).collect(),
+        }
+    }
+}
+
+
+/// Malformed patch: 1 based line number of the patch and description of the problem
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+pub struct PatchError
+{
+    pub line    : usize,
+    pub message : String,
+}
+
+impl fmt::Display for PatchError
+{
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+
+/// Parses all file patches of the text, lines end with "\n" or "\r\n" (see module documentation)
+pub fn parse(text : &str) -> Result<Vec<FilePatch>, PatchError>
+{
+    let mut parser = Parser { lines : text.lines().collect(), pos : 0 };
+    let mut patches = Vec::new();
+
+    while parser.pos < parser.lines.len()
+    {
+        let line = parser.lines[parser.pos];
+        if line.starts_with("diff --git ") {
+            patches.push(parser.git_patch()?);
+        }
+        else if line.starts_with("--- ") && parser.next_starts_with("+++ ") {
+            let mut patch = FilePatch::default();
+            parser.unified_patch(&mut patch)?;
+            patches.push(patch);
+        }
+        else if line.starts_with("*** ") && !line.starts_with(CONTEXT_SEPARATOR) && parser.next_starts_with("--- ") {
+            let mut patch = FilePatch::default();
+            parser.context_patch(&mut patch)?;
+            patches.push(patch);
+        }
+        else {
+            parser.pos += 1;
+        }
+    }
+
+    Ok(patches)
+}
+
+
+/// Presents hunks of the patch, changed lines are compared by characters
+#[allow(dead_code)]
+pub fn present(patch : &FilePatch, presenter : &mut dyn Presenter) -> std::result::Result<usize, std::io::Error>
+{
+    let mut sd = StringDiff::new();
+    let mut result = Vec::new();
+    let mut bytes_written = presenter.header(patch.old_name.as_deref().unwrap_or(DEV_NULL), patch.new_name.as_deref().unwrap_or(DEV_NULL))?;
+
+    for hunk in &patch.hunks
+    {
+        let (mut old_idx, mut new_idx) = (hunk.old_start, hunk.new_start);
+        let mut i = 0;
+        while i < hunk.lines.len()
+        {
+            if hunk.lines[i].kind == LineKind::Context
+            {
+                bytes_written += presenter.present_unchanged(&hunk.lines[i].text, (old_idx + 1) as u32, (new_idx + 1) as u32)?;
+                old_idx += 1;
+                new_idx += 1;
+                i += 1;
+                continue;
+            }
+
+            //change: removed lines paired with inserted ones
+            let removed : Vec<&str> = hunk.lines[i ..].iter().take_while(|l| l.kind == LineKind::Remove).map(|l| l.text.as_str()).collect();
+            let inserted : Vec<&str> = hunk.lines[i + removed.len() ..].iter().take_while(|l| l.kind == LineKind::Insert).map(|l| l.text.as_str()).collect();
+            for k in 0 .. std::cmp::max(removed.len(), inserted.len())
+            {
+                let line1 = removed.get(k).cloned().unwrap_or("");
+                let line2 = inserted.get(k).cloned().unwrap_or("");
+                let old_line = if k < removed.len() { Some((old_idx + k + 1) as u32) } else { None };
+                let new_line = if k < inserted.len() { Some((new_idx + k + 1) as u32) } else { None };
+
+                sd.get_diff_into(line1, line2, &mut result);
+                bytes_written += presenter.present(line1, line2, old_line, new_line, &result)?;
+            }
+
+            old_idx += removed.len();
+            new_idx += inserted.len();
+            i += removed.len() + inserted.len();
+        }
+    }
+
+    bytes_written += presenter.footer()?;
+    Ok(bytes_written)
+}
+
+
+struct Parser<'a>
+{
+    lines : Vec<&'a str>,
+    pos   : usize,
+}
+
+impl<'a> Parser<'a>
+{
+    fn error(&self, message : &str) -> PatchError
+    {
+        PatchError { line : self.pos + 1, message : message.to_string() }
+    }
+
+    fn next_starts_with(&self, prefix : &str) -> bool
+    {
+        self.lines.get(self.pos + 1).is_some_and(|l| l.starts_with(prefix))
+    }
+
+    fn current_starts_with(&self, prefix : &str) -> bool
+    {
+        self.lines.get(self.pos).is_some_and(|l| l.starts_with(prefix))
+    }
+
+    ///Git patch: "diff --git" line, extended headers and optional unified patch
+    fn git_patch(&mut self) -> Result<FilePatch, PatchError>
+    {
+        let names = &self.lines[self.pos]["diff --git ".len() ..];
+        let (old_name, new_name) = match names.find(" b/") {
+            Some(idx) => (&names[.. idx], &names[idx + 1 ..]),
+            None => match names.split_once(' ') {
+                Some(pair) => pair,
+                None => return Err(self.error("file names expected")),
+            },
+        };
+
+        let mut patch = FilePatch { old_name : Some(unquote(old_name)), new_name : Some(unquote(new_name)), .. FilePatch::default() };
+        self.pos += 1;
+
+        while self.pos < self.lines.len()
+        {
+            let line = self.lines[self.pos];
+            if let Some(mode) = line.strip_prefix("old mode ") {
+                patch.old_mode = Some(self.mode(mode)?);
+            }
+            else if let Some(mode) = line.strip_prefix("new mode ") {
+                patch.new_mode = Some(self.mode(mode)?);
+            }
+            else if let Some(mode) = line.strip_prefix("deleted file mode ") {
+                patch.old_mode = Some(self.mode(mode)?);
+                patch.new_name = None;
+            }
+            else if let Some(mode) = line.strip_prefix("new file mode ") {
+                patch.new_mode = Some(self.mode(mode)?);
+                patch.old_name = None;
+            }
+            else if let Some(name) = line.strip_prefix("rename from ") {
+                patch.rename = true;
+                patch.old_name = Some(unquote(name));
+            }
+            else if let Some(name) = line.strip_prefix("rename to ") {
+                patch.rename = true;
+                patch.new_name = Some(unquote(name));
+            }
+            else if let Some(name) = line.strip_prefix("copy from ") {
+                patch.copy = true;
+                patch.old_name = Some(unquote(name));
+            }
+            else if let Some(name) = line.strip_prefix("copy to ") {
+                patch.copy = true;
+                patch.new_name = Some(unquote(name));
+            }
+            else if line.starts_with("Binary files ") || line == "GIT binary patch" {
+                patch.binary = true;
+            }
+            else if line.starts_with("--- ") && self.next_starts_with("+++ ") {
+                //unified part keeps names of rename and copy headers
+                let (old_name, new_name) = (patch.old_name.clone(), patch.new_name.clone());
+                self.unified_patch(&mut patch)?;
+                if patch.rename || patch.copy {
+                    patch.old_name = old_name;
+                    patch.new_name = new_name;
+                }
+                break;
+            }
+            else if line.starts_with("diff --git ") {
+                break;
+            }
+            //"index", "similarity index" and lines of binary patch carry nothing to keep
+            self.pos += 1;
+        }
+
+        Ok(patch)
+    }
+
+    ///Unified patch: "---" and "+++" headers and "@@" hunks
+    fn unified_patch(&mut self, patch : &mut FilePatch) -> Result<(), PatchError>
+    {
+        patch.old_name = file_name(&self.lines[self.pos][4 ..]);
+        patch.new_name = file_name(&self.lines[self.pos + 1][4 ..]);
+        self.pos += 2;
+
+        while self.current_starts_with("@@ ")
+        {
+            let hunk = self.unified_hunk()?;
+            patch.hunks.push(hunk);
+        }
+
+        Ok(())
+    }
+
+    fn unified_hunk(&mut self) -> Result<Hunk, PatchError>
+    {
+        let header = self.lines[self.pos];
+        let ranges : Vec<&str> = header.split(' ').skip(1).take(2).collect();
+        let (old_start, old_count) = match ranges.first().and_then(|r| r.strip_prefix('-')).and_then(range) {
+            Some(r) => r,
+            None => return Err(self.error("malformed hunk header")),
+        };
+        let (new_start, new_count) = match ranges.get(1).and_then(|r| r.strip_prefix('+')).and_then(range) {
+            Some(r) => r,
+            None => return Err(self.error("malformed hunk header")),
+        };
+        self.pos += 1;
+
+        let mut lines = Vec::new();
+        let (mut old_left, mut new_left) = (old_count, new_count);
+        let (mut old_no_newline, mut new_no_newline) = (false, false);
+        while old_left > 0 || new_left > 0
+        {
+            let line = match self.lines.get(self.pos) {
+                Some(line) => *line,
+                None => return Err(self.error("unexpected end of hunk")),
+            };
+
+            let (kind, text) = match line.chars().next()
+            {
+                Some(' ') => (LineKind::Context, &line[1 ..]),
+                None      => (LineKind::Context, ""), //blank context line with trailing space removed
+                Some('-') => (LineKind::Remove, &line[1 ..]),
+                Some('+') => (LineKind::Insert, &line[1 ..]),
+                Some('\\') => {
+                    let (old, new) = no_newline(lines.last());
+                    old_no_newline |= old;
+                    new_no_newline |= new;
+                    self.pos += 1;
+                    continue;
+                },
+                _ => return Err(self.error("unexpected line in hunk")),
+            };
+
+            if kind != LineKind::Insert {
+                if old_left == 0 { return Err(self.error("hunk has more lines than its header says")); }
+                old_left -= 1;
+            }
+            if kind != LineKind::Remove {
+                if new_left == 0 { return Err(self.error("hunk has more lines than its header says")); }
+                new_left -= 1;
+            }
+            lines.push(ScriptLine::new(kind, text));
+            self.pos += 1;
+        }
+
+        //"\ No newline at end of file" of the last line
+        if self.current_starts_with("\\") {
+            let (old, new) = no_newline(lines.last());
+            old_no_newline |= old;
+            new_no_newline |= new;
+            self.pos += 1;
+        }
+
+        Ok(Hunk { old_start : start_index(old_start, old_count), old_count, new_start : start_index(new_start, new_count), new_count, lines,
+                  old_no_newline, new_no_newline })
+    }
+
+    ///Context patch: "***" and "---" headers and hunks separated by "***************"
+    fn context_patch(&mut self, patch : &mut FilePatch) -> Result<(), PatchError>
+    {
+        patch.old_name = file_name(&self.lines[self.pos][4 ..]);
+        patch.new_name = file_name(&self.lines[self.pos + 1][4 ..]);
+        self.pos += 2;
+
+        while self.current_starts_with(CONTEXT_SEPARATOR)
+        {
+            self.pos += 1;
+            let hunk = self.context_hunk()?;
+            patch.hunks.push(hunk);
+        }
+
+        Ok(())
+    }
+
+    fn context_hunk(&mut self) -> Result<Hunk, PatchError>
+    {
+        let old_range = self.context_range("*** ", " ****")?;
+        self.pos += 1;
+
+        let mut old_section = Vec::new();
+        let mut old_no_newline = false;
+        while !self.current_starts_with("--- ")
+        {
+            if self.current_starts_with("\\") {
+                old_no_newline = true;
+                self.pos += 1;
+                continue;
+            }
+            match self.lines.get(self.pos).and_then(|l| section_line(l)) {
+                Some(line) => old_section.push(line),
+                None => return Err(self.error("unexpected line in hunk")),
+            }
+            self.pos += 1;
+        }
+
+        let new_range = self.context_range("--- ", " ----")?;
+        self.pos += 1;
+
+        //single number range may be empty, section lines tell
+        let mut new_section = Vec::new();
+        while new_section.len() < new_range.1
+        {
+            match self.lines.get(self.pos).and_then(|l| section_line(l)) {
+                Some(line) => new_section.push(line),
+                None => break,
+            }
+            self.pos += 1;
+        }
+        let mut new_no_newline = self.current_starts_with("\\");
+        if new_no_newline {
+            self.pos += 1;
+        }
+
+        //section without changes is omitted, its lines are the context of the other one
+        if old_section.is_empty() {
+            old_no_newline = new_no_newline && new_section.last().is_some_and(|l| l.0 == ' ');
+            old_section = new_section.iter().filter(|l| l.0 == ' ').cloned().collect();
+        }
+        if new_section.is_empty() {
+            new_no_newline = old_no_newline && old_section.last().is_some_and(|l| l.0 == ' ');
+            new_section = old_section.iter().filter(|l| l.0 == ' ').cloned().collect();
+        }
+
+        let lines = match merge_sections(&old_section, &new_section) {
+            Some(lines) => lines,
+            None => return Err(self.error("sections of hunk do not match")),
+        };
+
+        let old_count = lines.iter().filter(|l| l.kind != LineKind::Insert).count();
+        let new_count = lines.iter().filter(|l| l.kind != LineKind::Remove).count();
+        Ok(Hunk { old_start : start_index(old_range.0, old_count), old_count, new_start : start_index(new_range.0, new_count), new_count, lines,
+                  old_no_newline, new_no_newline })
+    }
+
+    ///First line and count of lines of "*** 1,3 ****" like range
+    fn context_range(&self, prefix : &str, suffix : &str) -> Result<(usize, usize), PatchError>
+    {
+        let range = self.lines.get(self.pos)
+                        .and_then(|l| l.strip_prefix(prefix))
+                        .and_then(|l| l.strip_suffix(suffix))
+                        .and_then(|r| {
+                            match r.split_once(',') {
+                                Some((first, last)) => {
+                                    let (first, last) = (first.parse::<usize>().ok()?, last.parse::<usize>().ok()?);
+                                    Some((first, (last + 1).saturating_sub(first)))
+                                },
+                                None => r.parse::<usize>().ok().map(|first| (first, 1)),
+                            }
+                        });
+
+        range.ok_or_else(|| self.error("malformed hunk range"))
+    }
+
+    fn mode(&self, mode : &str) -> Result<u32, PatchError>
+    {
+        u32::from_str_radix(mode.trim(), 8).map_err(|_| self.error("malformed file mode"))
+    }
+}
+
+
+///Start and count of unified range "12,3" or "12"
+fn range(text : &str) -> Option<(usize, usize)>
+{
+    match text.split_once(',') {
+        Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+        None => Some((text.parse().ok()?, 1)),
+    }
+}
+
+///0 based index of the first line of hunk, empty hunk follows the line of its 1 based start
+fn start_index(start : usize, count : usize) -> usize
+{
+    if count == 0 { start } else { start.saturating_sub(1) }
+}
+
+///Sides of "\ No newline at end of file" marker (old, new): the sides of the line it follows
+fn no_newline(line : Option<&ScriptLine>) -> (bool, bool)
+{
+    match line.map(|l| &l.kind)
+    {
+        Some(LineKind::Context) => (true, true),
+        Some(LineKind::Remove)  => (true, false),
+        Some(LineKind::Insert)  => (false, true),
+        None => (false, false),
+    }
+}
+
+///Name of file header without timestamp, None for /dev/null
+fn file_name(header : &str) -> Option<String>
+{
+    let name = header.split('\t').next().unwrap_or(header).trim_end();
+    if name == DEV_NULL { None } else { Some(unquote(name)) }
+}
+
+fn unquote(name : &str) -> String
+{
+    name.strip_prefix('"').and_then(|n| n.strip_suffix('"')).unwrap_or(name).to_string()
+}
+
+///Marker and text of context section line: "  text", "- text", "+ text", "! text"
+fn section_line(line : &str) -> Option<(char, String)>
+{
+    let mut chars = line.chars();
+    let marker = chars.next()?;
+    if !" -+!".contains(marker) || chars.next().is_some_and(|c| c != ' ') {
+        return None;
+    }
+
+    Some((marker, line.get(2 ..).unwrap_or("").to_string()))
+}
+
+///Merges old and new sections of context hunk into single script
+fn merge_sections(old : &[(char, String)], new : &[(char, String)]) -> Option<Vec<ScriptLine>>
+{
+    let mut lines = Vec::with_capacity(old.len() + new.len());
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len()
+    {
+        let old_marker = old.get(i).map(|l| l.0);
+        let new_marker = new.get(j).map(|l| l.0);
+
+        if old_marker == Some('-') {
+            lines.push(ScriptLine::new(LineKind::Remove, &old[i].1));
+            i += 1;
+        }
+        else if new_marker == Some('+') {
+            lines.push(ScriptLine::new(LineKind::Insert, &new[j].1));
+            j += 1;
+        }
+        else if old_marker == Some('!') || new_marker == Some('!') {
+            while i < old.len() && old[i].0 == '!' {
+                lines.push(ScriptLine::new(LineKind::Remove, &old[i].1));
+                i += 1;
+            }
+            while j < new.len() && new[j].0 == '!' {
+                lines.push(ScriptLine::new(LineKind::Insert, &new[j].1));
+                j += 1;
+            }
+        }
+        else if old_marker == Some(' ') && new_marker == Some(' ') {
+            lines.push(ScriptLine::new(LineKind::Context, &old[i].1));
+            i += 1;
+            j += 1;
+        }
+        else {
+            return None;
+        }
+    }
+
+    Some(lines)
+}
+
+
+#[cfg(test)]
+mod tests
+{
+    use std::rc::Rc;
+    use std::cell::RefCell;
+    use ::diff::patch::*;
+    use ::diff::hunk::{HunkRecorder,hunks};
+    use ::diff::unified::{UnifiedPresenter,format_hunk};
+    use ::diff::context::ContextPresenter;
+    use ::diff::presenter::{Presenter,SharedBuffer};
+
+    fn script() -> Vec<ScriptLine>
+    {
+        let mut rec = HunkRecorder::new();
+        rec.record("", "first", None, Some(1), &[]);
+        for (i, line) in ["a", "b", "c", "d", "e", "f", "g", "h"].iter().enumerate() {
+            rec.record_unchanged(line);
+            if i == 1 {
+                rec.record("old", "new", Some(4), Some(5), &[]);
+                rec.record("removed", "", Some(5), None, &[]);
+            }
+        }
+        rec.record("", "last", None, Some(13), &[]);
+        rec.take()
+    }
+
+    fn render(presenter : &mut dyn Presenter, buffer : &Rc<RefCell<Vec<u8>>>) -> String
+    {
+        presenter.header("a.txt\t2024-01-01 00:00:00", "b.txt").unwrap();
+        for line in script()
+        {
+            match line.kind {
+                LineKind::Context => presenter.present_unchanged(&line.text, 0, 0).unwrap(),
+                LineKind::Remove  => presenter.present(&line.text, "", Some(1), None, &[]).unwrap(),
+                LineKind::Insert  => presenter.present("", &line.text, None, Some(1), &[]).unwrap(),
+            };
+        }
+        presenter.footer().unwrap();
+
+        let out = buffer.borrow().clone();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn unified_and_context_round_trip()
+    {
+        let expected = hunks(&script(), 2);
+
+        let buffer = Rc::new(RefCell::new(Vec::new()));
+        let text = render(&mut UnifiedPresenter::new(Box::new(SharedBuffer(buffer.clone())), 2), &buffer);
+        let patches = parse(&text).unwrap();
+        assert_eq!(patches.len(), 1);
+        assert_eq!(patches[0].old_name, Some(String::from("a.txt")));
+        assert_eq!(patches[0].new_name, Some(String::from("b.txt")));
+        assert_eq!(patches[0].hunks, expected);
+
+        let buffer = Rc::new(RefCell::new(Vec::new()));
+        let text = render(&mut ContextPresenter::new(Box::new(SharedBuffer(buffer.clone())), 2), &buffer);
+        let patches = parse(&text).unwrap();
+        assert_eq!(patches.len(), 1);
+        assert_eq!(patches[0].hunks, expected);
+
+        assert_eq!(patches[0].inverted().inverted(), patches[0]);
+    }
+
+    #[test]
+    fn git_patch()
+    {
+        let text = "commit message\n\
+                    diff --git a/old.rs b/new.rs\n\
+                    similarity index 90%\n\
+                    rename from old.rs\n\
+                    rename to new.rs\n\
+                    index 1234567..89abcde 100644\n\
+                    --- a/old.rs\n\
+                    +++ b/new.rs\n\
+                    @@ -1,2 +1,2 @@\n\
+                    \x20fn main() {\n\
+                    -}\n\
+                    +}\n\
+                    \\ No newline at end of file\n\
+                    diff --git a/run.sh b/run.sh\n\
+                    old mode 100644\n\
+                    new mode 100755\n\
+                    diff --git a/added.txt b/added.txt\n\
+                    new file mode 100644\n\
+                    index 0000000..e69de29\n\
+                    --- /dev/null\n\
+                    +++ b/added.txt\n\
+                    @@ -0,0 +1 @@\n\
+                    +text\n\
+                    diff --git a/logo.png b/logo.png\n\
+                    deleted file mode 100644\n\
+                    Binary files a/logo.png and /dev/null differ\n";
+
+        let patches = parse(text).unwrap();
+        assert_eq!(patches.len(), 4);
+
+        assert!(patches[0].rename);
+        assert_eq!((patches[0].old_name.as_deref(), patches[0].new_name.as_deref()), (Some("old.rs"), Some("new.rs")));
+        assert_eq!(patches[0].hunks[0].lines.len(), 3);
+        assert_eq!((patches[0].hunks[0].old_no_newline, patches[0].hunks[0].new_no_newline), (false, true));
+
+        assert_eq!((patches[1].old_mode, patches[1].new_mode), (Some(0o100644), Some(0o100755)));
+        assert!(patches[1].hunks.is_empty());
+
+        assert_eq!((patches[2].old_name.as_deref(), patches[2].new_name.as_deref()), (None, Some("b/added.txt")));
+        assert_eq!(patches[2].hunks[0], Hunk { old_start : 0, old_count : 0, new_start : 0, new_count : 1, lines : vec![ScriptLine::new(LineKind::Insert, "text")], .. Hunk::default() });
+
+        assert!(patches[3].binary);
+        assert_eq!((patches[3].old_name.as_deref(), patches[3].new_name.as_deref()), (Some("a/logo.png"), None));
+    }
+
+    #[test]
+    fn no_newline_markers()
+    {
+        let text = "*** a.txt\n--- b.txt\n***************\n*** 1,2 ****\n  a\n! b\n\\ No newline at end of file\n--- 1,2 ----\n  a\n! c\n";
+        let patches = parse(text).unwrap();
+        let hunk = &patches[0].hunks[0];
+        assert_eq!((hunk.old_no_newline, hunk.new_no_newline), (true, false));
+
+        //unified format writes the marker after the last old line
+        let unified = format!("--- a.txt\n+++ b.txt\n{}", format_hunk(hunk));
+        assert_eq!(unified, "--- a.txt\n+++ b.txt\n@@ -1,2 +1,2 @@\n a\n-b\n\\ No newline at end of file\n+c\n");
+        assert_eq!(&parse(&unified).unwrap()[0].hunks[0], hunk);
+    }
+
+    #[test]
+    fn malformed_patch()
+    {
+        let text = "--- a.txt\n+++ b.txt\n@@ -1,2 +1,2 @@\n a\n-b\n";
+        assert_eq!(parse(text), Err(PatchError { line : 6, message : String::from("unexpected end of hunk") }));
+
+        let text = "--- a.txt\n+++ b.txt\n@@ -1 +x @@\n";
+        assert_eq!(parse(text).unwrap_err().line, 3);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip()
+    {
+        extern crate serde_json;
+
+        let text = "diff --git a/x.txt b/y.txt\nold mode 100644\nnew mode 100755\nrename from x.txt\nrename to y.txt\n--- a/x.txt\n+++ b/y.txt\n@@ -1,2 +1,2 @@\n a\n-b\n+c\n\\ No newline at end of file\n";
+        let patches = parse(text).unwrap();
+        assert!(patches[0].rename && patches[0].hunks[0].new_no_newline);
+
+        let json = serde_json::to_string(&patches).unwrap();
+        let back : Vec<FilePatch> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, patches);
+    }
+
+    #[test]
+    fn carriage_returns_are_terminators()
+    {
+        //patch written with CRLF terminators is the same patch
+        let text = "--- a.txt\n+++ b.txt\n@@ -1,2 +1,2 @@\n a\n-b\n+c\n";
+        assert_eq!(parse(&text.replace('\n', "\r\n")).unwrap(), parse(text).unwrap());
+
+        //change of terminator only removes and inserts the same line
+        let patches = parse("--- a.txt\n+++ b.txt\n@@ -1 +1 @@\n-a\r\n+a\n").unwrap();
+        assert_eq!(patches[0].hunks[0].lines, vec![ScriptLine::new(LineKind::Remove, "a"), ScriptLine::new(LineKind::Insert, "a")]);
+    }
+}
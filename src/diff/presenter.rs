@@ -1,25 +1,194 @@
 use std;
 use std::io::Write;
+use std::cmp::max;
+use std::collections::VecDeque;
 
 use diff::difflib::DiffCell;
 use diff::difflib::DiffOperation;
+use diff::linediff::LineCell;
+use diff::unified::DEFAULT_CONTEXT;
+
+
+///Base presenter: receives the difference line by line and writes it in its own format.
+///Line numbers are 1 based and None when the line is absent on that side, 
+///or the comparison is not line based at all (plain strings).
+pub trait Presenter
+{
+    ///Presents names of compared strings or files, called once before anything else 
+    fn header(&mut self, _old : &str, _new : &str) -> std::result::Result<usize, std::io::Error>;
+
+    ///Presents changed line (or string) with its character level difference
+    fn present(&mut self, _old : &str, _new : &str, old_line : Option<u32>, new_line : Option<u32>, result : &[DiffCell]) -> std::result::Result<usize, std::io::Error>;
+
+    ///Presents single line of moved block, `source` is true for the line at its original place
+    ///and `other_line_num` is the line number at the other side of the move 
+    fn present_moved(&mut self, line : &str, line_num : u32, other_line_num : u32, source : bool) -> std::result::Result<usize, std::io::Error>;
+
+    ///Presents line which is the same in both files, ignored by default
+    fn present_unchanged(&mut self, _line : &str, _old_line : u32, _new_line : u32) -> std::result::Result<usize, std::io::Error>
+    {
+        Ok(0)
+    }
+
+    ///Tells whether the last line of old and new input has no line terminator,
+    ///called before the lines are presented. Ignored by default
+    fn set_no_newline(&mut self, _old : bool, _new : bool)
+    {
+    }
+
+    ///Takes line edit script of compared files as a whole, for presenters summarizing it.
+    ///Returns false when lines have to be presented one by one, as by default
+    fn present_script(&mut self, _old : &str, _new : &str, _script : &[LineCell]) -> bool
+    {
+        false
+    }
+
+    ///Writes message about compared inputs, like a file present in one directory only
+    fn present_message(&mut self, text : &str) -> std::result::Result<usize, std::io::Error>;
+
+    ///Completes the output, called once after everything else 
+    fn footer(&mut self) -> std::result::Result<usize, std::io::Error>
+    {
+        Ok(0)
+    }
+}
+
+
+///Row received by a presenter, kept by presenters until it is known whether it is shown
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+pub enum Row
+{
+    Unchanged { line : String, old_line : u32, new_line : u32 },
+    Changed   { old : String, new : String, old_line : Option<u32>, new_line : Option<u32>, result : Vec<DiffCell> },
+    Moved     { line : String, line_num : u32, other_line_num : u32, source : bool },
+}
+
+///Row released by ContextWindow
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub enum Released
+{
+    Shown(Row),  //changed or moved row, or unchanged one in context of a change
+    Hidden(Row), //unchanged row too far from changes
+    Gap,         //next hunk starts, after hidden rows
+}
+
+///Groups rows into hunks as they come: changed and moved rows with up to `context` unchanged rows
+///around them, the same as group_opcodes. Only up to `context` unchanged rows, which may turn into
+///context of the next change, are kept
+pub struct ContextWindow
+{
+    context : usize,
+    after   : Option<usize>, //unchanged rows since the last change, None before the first one
+    hidden  : bool,          //some unchanged rows since the last change were hidden
+    window  : VecDeque<Row>, //unchanged rows which may still be shown before the next change
+}
+
+impl ContextWindow
+{
+    pub fn new(context : usize) -> ContextWindow
+    {
+        ContextWindow { context, after : None, hidden : false, window : VecDeque::new() }
+    }
+
+    ///Tells whether any change was pushed since the start or last finish
+    pub fn is_changed(&self) -> bool
+    {
+        self.after.is_some()
+    }
+
+    ///Takes changed or moved row, releases it with unchanged rows kept before it
+    pub fn push_change(&mut self, row : Row) -> Vec<Released>
+    {
+        let mut released = Vec::new();
+        if self.hidden && self.after.is_some() {
+            released.push(Released::Gap);
+        }
+        released.extend(self.window.drain(..).map(Released::Shown));
+        released.push(Released::Shown(row));
+
+        self.after  = Some(0);
+        self.hidden = false;
+        released
+    }
+
+    ///Takes unchanged row, releases it when it is context after the last change,
+    ///or the oldest kept row when it is too far from both changes
+    pub fn push_unchanged(&mut self, row : Row) -> Vec<Released>
+    {
+        match self.after
+        {
+            Some(count) if count < self.context => {
+                self.after = Some(count + 1);
+                vec![Released::Shown(row)]
+            },
+            _ => {
+                self.window.push_back(row);
+                if self.window.len() <= self.context {
+                    return Vec::new();
+                }
+                self.hidden = true;
+                self.window.pop_front().map(Released::Hidden).into_iter().collect()
+            },
+        }
+    }
+
+    ///Releases kept rows as hidden, no change follows them, and starts over
+    pub fn finish(&mut self) -> Vec<Released>
+    {
+        self.after  = None;
+        self.hidden = false;
+        self.window.drain(..).map(Released::Hidden).collect()
+    }
+}
+
+
+///Writer which keeps written bytes accessible after the presenter took it, used by presenter tests
+#[cfg(test)]
+pub struct SharedBuffer(pub std::rc::Rc<std::cell::RefCell<Vec<u8>>>);
+
+#[cfg(test)]
+impl std::io::Write for SharedBuffer
+{
+    fn write(&mut self, buf : &[u8]) -> std::io::Result<usize>
+    {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()>
+    {
+        Ok(())
+    }
+}
 
 
 pub struct DiffPresenter    
 {   
-    writer : Box<std::io::Write>,
+    writer : Box<dyn std::io::Write>,
+    window : ContextWindow, //unchanged lines shown around changes
 }
 
-///Built-in impementation of DiffPresenter
 impl DiffPresenter    
 {
+    pub fn new (_w : Box<dyn std::io::Write>) -> DiffPresenter        
+    {
+         DiffPresenter::with_context(_w, DEFAULT_CONTEXT)
+    }
 
-    pub fn new (_w : Box<std::io::Write>) -> DiffPresenter        
+    pub fn with_context(_w : Box<dyn std::io::Write>, context : usize) -> DiffPresenter
     {
-         DiffPresenter{ writer : _w }        
+         DiffPresenter{ writer : _w, window : ContextWindow::new(context) }
     }
+}
+
+///Built-in impementation of Presenter
+impl Presenter for DiffPresenter    
+{
 
-    pub fn header(&mut self, _old : &str, _new : &str)-> std::result::Result<usize, std::io::Error>
+    fn header(&mut self, _old : &str, _new : &str)-> std::result::Result<usize, std::io::Error>
     {
         let mut bytes_written = self.writer.write("/***********************************************************\n".as_bytes())?;
         bytes_written += self.writer.write("* Difference between : \n".as_bytes())?;
@@ -31,12 +200,69 @@ impl DiffPresenter
         Ok(bytes_written)
     }
 
-    pub fn present(&mut self, _old : &str, _new : &str, line_num : u32,  result : &[DiffCell]) -> std::result::Result<usize, std::io::Error>
+    fn present(&mut self, _old : &str, _new : &str, old_line : Option<u32>, new_line : Option<u32>, result : &[DiffCell]) -> std::result::Result<usize, std::io::Error>
+    {
+        let released = self.window.push_change(Row::Changed { old : _old.to_string(), new : _new.to_string(), old_line, new_line, result : result.to_vec() });
+        self.write_released(released)
+    }
+
+    fn present_moved(&mut self, line : &str, line_num : u32, other_line_num : u32, source : bool) -> std::result::Result<usize, std::io::Error>
     {
+        let released = self.window.push_change(Row::Moved { line : line.to_string(), line_num, other_line_num, source });
+        self.write_released(released)
+    }
+
+    fn present_unchanged(&mut self, line : &str, old_line : u32, new_line : u32) -> std::result::Result<usize, std::io::Error>
+    {
+        let released = self.window.push_unchanged(Row::Unchanged { line : line.to_string(), old_line, new_line });
+        self.write_released(released)
+    }
+
+    fn present_message(&mut self, text : &str) -> std::result::Result<usize, std::io::Error>
+    {
+        self.writer.write(format!("{}\n", text).as_bytes())
+    }
+
+    fn footer(&mut self) -> std::result::Result<usize, std::io::Error>
+    {
+        self.window.finish();
+        self.writer.flush()?;
+
+        Ok(0)
+    }
+}
+
+impl DiffPresenter    
+{
+    ///Writes changes with their context as soon as they are released, hunks are separated by "..." line
+    fn write_released(&mut self, released : Vec<Released>) -> std::result::Result<usize, std::io::Error>
+    {
+        let mut bytes_written = 0;
+        for item in released
+        {
+            bytes_written += match item
+            {
+                Released::Shown(Row::Unchanged { ref line, old_line, .. }) => self.write_unchanged(line, old_line)?,
+                Released::Shown(Row::Changed { ref old, ref new, old_line, new_line, ref result }) => self.write_changed(old, new, old_line, new_line, result)?,
+                Released::Shown(Row::Moved { ref line, line_num, other_line_num, source }) => self.write_moved(line, line_num, other_line_num, source)?,
+                Released::Hidden(_) => 0,
+                Released::Gap => self.writer.write("\n...\n".as_bytes())?,
+            };
+        }
+
+        Ok(bytes_written)
+    }
+
+    ///Writes changed line (or string), every character level change separately
+    fn write_changed(&mut self, _old : &str, _new : &str, old_line : Option<u32>, new_line : Option<u32>, result : &[DiffCell]) -> std::result::Result<usize, std::io::Error>
+    {
+        //line number in old file for removed and updated lines, in new one for inserted
+        let line_num = old_line.or(new_line).unwrap_or(0);
+
         let mut bytes_written = 0;
-        if result.len() > 0 
+        if !result.is_empty() 
         {
-            let red_diff_count = self.skip_redundant(&result);
+            let red_diff_count = self.skip_redundant(result);
             let iter = result.iter().skip(red_diff_count);
             for diff in iter
             {
@@ -45,15 +271,15 @@ impl DiffPresenter
                 
                 if diff.operation == DiffOperation::Insert  
                 {                
-                    bytes_written += self.present_insert(_old, _new, &diff)?;        
+                    bytes_written += self.present_insert(_old, _new, diff)?;        
                 }
                 else if diff.operation == DiffOperation::Remove
                 {                          
-                    bytes_written += self.present_remove(_old, _new, &diff)?;
+                    bytes_written += self.present_remove(_old, _new, diff)?;
                 }  
-                else if diff.operation == DiffOperation::Update
+                else if diff.operation == DiffOperation::Update || diff.operation == DiffOperation::Transpose
                 {                
-                    bytes_written += self.present_udate(_old, _new, &diff)?;
+                    bytes_written += self.present_udate(_old, _new, diff)?;
                 }  
             }
         }
@@ -61,6 +287,32 @@ impl DiffPresenter
         Ok(bytes_written)
     }
 
+    ///Writes single line of moved block
+    fn write_moved(&mut self, line : &str, line_num : u32, other_line_num : u32, source : bool) -> std::result::Result<usize, std::io::Error>
+    {
+        let (direction, marker) = if source { ("to", "<") } else { ("from", ">") };
+
+        let mut bytes_written = self.writer.write("\n".as_bytes())?;
+        bytes_written += self.writer.write(format!("Line: {}, ===(m)=== moved {} line {}\n", line_num, direction, other_line_num).as_bytes())?;
+        bytes_written += self.writer.write(format!("{}\n", line).as_bytes())?;
+
+        let markers = marker.repeat(max(line.chars().count(), 1));
+        bytes_written += self.writer.write(format!("{}\n", markers).as_bytes())?;
+
+        Ok(bytes_written)
+    }
+
+    ///Writes context line
+    fn write_unchanged(&mut self, line : &str, old_line : u32) -> std::result::Result<usize, std::io::Error>
+    {
+        let mut bytes_written = self.writer.write("\n".as_bytes())?;
+        bytes_written += self.writer.write(format!("Line: {}, ==={}===\n", old_line, DiffOperation::None).as_bytes())?;
+        bytes_written += self.writer.write(format!("{}\n", line).as_bytes())?;
+
+        Ok(bytes_written)
+    }
+
+
     ///Skips redundant diff cells, by ignoring them 
     fn skip_redundant(&self, vec : &[DiffCell]) -> usize
     {
@@ -70,38 +322,37 @@ impl DiffPresenter
             let prev = &vec[i];
             let next = &vec[i + 1];
 
-            if (prev.operation == DiffOperation::Insert && next.operation == DiffOperation::Remove)
-                    || (prev.operation == DiffOperation::Remove && next.operation == DiffOperation::Insert)
+            if ((prev.operation == DiffOperation::Insert && next.operation == DiffOperation::Remove)
+                    || (prev.operation == DiffOperation::Remove && next.operation == DiffOperation::Insert))
+                    && prev.start == next.start && prev.count == next.count
             {
-                if prev.start == next.start && prev.count == next.count
-                {
-                    count_to_skip += 2; //skip both
-                }
-
+                count_to_skip += 2; //skip both
             }
         }
 
         count_to_skip
     }
 
-    ///Presents INSERT change 
+    ///Presents INSERT change, inserted characters are written at their place in the old string
     fn present_insert(&mut self, _old : &str, _new : &str, diff : &DiffCell) -> std::result::Result<usize, std::io::Error>
     {
-      
-        let out_new = _new.chars().skip(diff.start).take(diff.count).collect::<String>();
-        let mut bytes_written = self.writer.write(format!("{}{}\n", _old, out_new).as_bytes())?;
+        let at = diff.old_range().start;
+        let out_new    = _new.chars().skip(diff.new_start).take(diff.count).collect::<String>();
+        let old_before = _old.chars().take(at).collect::<String>();
+        let old_after  = _old.chars().skip(at).collect::<String>();
+        let mut bytes_written = self.writer.write(format!("{}{}{}\n", old_before, out_new, old_after).as_bytes())?;
 
-        let empty_before_change = std::iter::repeat(" ").take(_old.len()).collect::<String>(); 
+        let empty_before_change = " ".repeat(at); 
         if diff.count > 1 
         { 
-            let empty_change = std::iter::repeat(" ").take(diff.count - 2).collect::<String>(); //first pipe and last one have to be skiped  
+            let empty_change = " ".repeat(diff.count - 2); //first pipe and last one have to be skiped  
             bytes_written += self.writer.write(format!("{}|{}|\n", empty_before_change, empty_change).as_bytes())?;
         }
         else {
             bytes_written += self.writer.write(format!("{}|\n", empty_before_change).as_bytes())?;
         }
         
-        let ins = std::iter::repeat("+").take(diff.count).collect::<String>();         
+        let ins = "+".repeat(diff.count);         
         bytes_written += self.writer.write(format!("{}{}\n", empty_before_change, ins).as_bytes())?;      
         
         Ok(bytes_written)
@@ -112,17 +363,17 @@ impl DiffPresenter
     {
         let mut bytes_written = self.writer.write(format!("{}\n", _old).as_bytes())?;
 
-        let empty_before_change = std::iter::repeat(" ").take(diff.start).collect::<String>(); 
+        let empty_before_change = " ".repeat(diff.start); 
         if diff.count > 1 
         { 
-            let empty_change = std::iter::repeat(" ").take(diff.count - 2).collect::<String>(); //first pipe and last one have to be skiped  
+            let empty_change = " ".repeat(diff.count - 2); //first pipe and last one have to be skiped  
             bytes_written += self.writer.write(format!("{}|{}|\n", empty_before_change, empty_change).as_bytes())?;
         }
         else {
             bytes_written += self.writer.write(format!("{}|\n", empty_before_change).as_bytes())?;
         }
         
-        let dels = std::iter::repeat("x").take(diff.count).collect::<String>(); 
+        let dels = "x".repeat(diff.count); 
         bytes_written += self.writer.write(format!("{}{}\n", empty_before_change, dels).as_bytes())?;       
 
         Ok(bytes_written)       
@@ -132,14 +383,14 @@ impl DiffPresenter
     ///Presents UPDATE change
     fn present_udate(&mut self, _old : &str, _new : &str, diff : &DiffCell) -> std::result::Result<usize, std::io::Error>
     {
-        let out_new  = _new.chars().skip(diff.start).take(diff.count).collect::<String>();
+        let out_new  = _new.chars().skip(diff.new_start).take(diff.count).collect::<String>();
         let mut bytes_written = self.writer.write(format!("{}\n", _old).as_bytes())?;
 
         
-        let empty_before_change = std::iter::repeat(" ").take(diff.start).collect::<String>();          
+        let empty_before_change = " ".repeat(diff.start);          
         if diff.count > 1 
         { 
-            let empty_change = std::iter::repeat(" ").take(diff.count - 2).collect::<String>(); //first pipe and last one have to be skiped 
+            let empty_change = " ".repeat(diff.count - 2); //first pipe and last one have to be skiped 
             bytes_written += self.writer.write(format!("{}|{}|\n", empty_before_change, empty_change).as_bytes())?;
         }
         else 
@@ -156,3 +407,98 @@ impl DiffPresenter
 
 
 
+
+
+#[cfg(test)]
+mod tests
+{
+    use std::rc::Rc;
+    use std::cell::RefCell;
+    use ::diff::presenter::*;
+    use ::diff::difflib::StringDiff;
+
+    fn present(_old : &str, _new : &str) -> String
+    {
+        let out = Rc::new(RefCell::new(Vec::new()));
+        let mut presenter = DiffPresenter::new(Box::new(SharedBuffer(out.clone())));
+        let result = StringDiff::new().get_diff(_old, _new);
+        presenter.present(_old, _new, Some(1), Some(1), &result).unwrap();
+        presenter.footer().unwrap();
+
+        let bytes = out.borrow().clone();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    #[test]
+    fn inserted_after_removed()
+    {
+        //inserted character follows old character 5, but it is character 4 of the new string
+        let out = present("xabc_", "abc_y");
+        assert!(out.contains("===(i)===\nxabc_y\n     |\n     +\n"), "{}", out);
+    }
+
+    #[test]
+    fn inserted_inside_multibyte()
+    {
+        //characters before the insertion take 2 bytes each
+        let out = Rc::new(RefCell::new(Vec::new()));
+        let mut presenter = DiffPresenter::new(Box::new(SharedBuffer(out.clone())));
+        let result = vec![DiffCell { start : 3, new_start : 3, count : 1, operation : DiffOperation::Insert }];
+        presenter.present("żółw", "żółxw", Some(1), Some(1), &result).unwrap();
+        presenter.footer().unwrap();
+
+        let bytes = out.borrow().clone();
+        assert_eq!(String::from_utf8(bytes).unwrap(), "\nLine: 1, ===(i)===\nżółxw\n   |\n   +\n");
+    }
+
+    #[test]
+    fn changes_with_context()
+    {
+        let out = Rc::new(RefCell::new(Vec::new()));
+        let mut presenter = DiffPresenter::with_context(Box::new(SharedBuffer(out.clone())), 1);
+        let mut sd = StringDiff::new();
+        for n in 1 .. 10
+        {
+            match n {
+                2 | 8 => presenter.present("a", "b", Some(n), Some(n), &sd.get_diff("a", "b")).unwrap(),
+                _ => presenter.present_unchanged(&format!("line {}", n), n, n).unwrap(),
+            };
+        }
+        presenter.footer().unwrap();
+
+        //lines 4 - 6 are too far from both changes
+        let bytes = out.borrow().clone();
+        assert_eq!(String::from_utf8(bytes).unwrap(),
+                   "\nLine: 1, ===(-)===\nline 1\n\nLine: 2, ===(u)===\na\n|\nb\n\nLine: 3, ===(-)===\nline 3\n\n...\n\
+                    \nLine: 7, ===(-)===\nline 7\n\nLine: 8, ===(u)===\na\n|\nb\n\nLine: 9, ===(-)===\nline 9\n");
+
+    }
+
+    #[test]
+    fn hunks_written_before_footer()
+    {
+        let out = Rc::new(RefCell::new(Vec::new()));
+        let mut presenter = DiffPresenter::with_context(Box::new(SharedBuffer(out.clone())), 1);
+        presenter.present_unchanged("line 1", 1, 1).unwrap();
+        presenter.present_moved("y", 2, 5, true).unwrap();
+        presenter.present_unchanged("line 3", 3, 3).unwrap();
+        presenter.present_unchanged("line 4", 4, 4).unwrap();
+
+        //the hunk is complete, the last unchanged line may be context of the next change
+        let bytes = out.borrow().clone();
+        assert_eq!(String::from_utf8(bytes).unwrap(),
+                   "\nLine: 1, ===(-)===\nline 1\n\nLine: 2, ===(m)=== moved to line 5\ny\n<\n\nLine: 3, ===(-)===\nline 3\n");
+
+        let mut window = ContextWindow::new(0);
+        let row = Row::Unchanged { line : String::from("x"), old_line : 1, new_line : 1 };
+        assert_eq!(window.push_unchanged(row.clone()), vec![Released::Hidden(row.clone())]);
+        assert!(!window.is_changed());
+        let moved = Row::Moved { line : String::from("y"), line_num : 2, other_line_num : 5, source : true };
+        assert_eq!(window.push_change(moved.clone()), vec![Released::Shown(moved.clone())]);
+        assert_eq!(window.push_unchanged(row.clone()), vec![Released::Hidden(row)]);
+        assert_eq!(window.push_change(moved.clone()), vec![Released::Gap, Released::Shown(moved)]);
+        assert!(window.is_changed());
+        assert_eq!(window.finish(), vec![]);
+        assert!(!window.is_changed());
+    }
+}
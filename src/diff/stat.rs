@@ -0,0 +1,314 @@
+//! Diffstat: summary of the difference instead of the difference itself
+//! Counts inserted and deleted lines of every compared pair straight from the
+//! line edit script, without character level comparison of changed lines, and writes
+//! them like git does:
+//!
+//!   --stat       a.txt | 5 +++--        per file histogram and total line
+//!                1 file changed, 3 insertions(+), 2 deletions(-)
+//!   --numstat    3       2       a.txt  per file numbers, tab separated
+//!   --shortstat  total line only
+//!
+//! Moved lines are counted as deleted at the old place and inserted at the new one.
+use std;
+use std::io::Write;
+use std::cmp::max;
+
+use diff::difflib::{DiffCell,DiffOperation};
+use diff::linediff::LineCell;
+use diff::presenter::Presenter;
+
+
+const STAT_WIDTH : usize = 80; //width of --stat output line, as git uses for non terminal output
+
+
+/// Output mode
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+pub enum StatMode
+{
+    Stat,
+    NumStat,
+    ShortStat,
+}
+
+
+/// Inserted and deleted lines of one compared pair
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+pub struct FileStat
+{
+    pub name       : String,
+    pub insertions : usize,
+    pub deletions  : usize,
+}
+
+impl FileStat
+{
+    /// Counts lines of the edit script, `name` is shown as "old => new" when names differ
+    pub fn from_script(_old : &str, _new : &str, script : &[LineCell]) -> FileStat
+    {
+        let mut stat = FileStat { name : display_name(_old, _new), insertions : 0, deletions : 0 };
+        for cell in script
+        {
+            match cell.operation
+            {
+                DiffOperation::Insert => stat.insertions += cell.count,
+                DiffOperation::Remove => stat.deletions += cell.count,
+                DiffOperation::Moved { .. } => {
+                    if cell.is_move_source() { stat.deletions += cell.count } else { stat.insertions += cell.count }
+                },
+                _ => {},
+            }
+        }
+
+        stat
+    }
+
+    pub fn changes(&self) -> usize
+    {
+        self.insertions + self.deletions
+    }
+}
+
+
+/// Summarizing presenter: collects FileStat of every compared pair and writes them
+/// all in footer. Files are added with `add_script` (Presenter::present_script), plain strings through Presenter::present,
+/// where changed string counts as 1 deleted and 1 inserted line.
+pub struct StatPresenter
+{
+    writer : Box<dyn std::io::Write>,
+    mode   : StatMode,
+    files  : Vec<FileStat>,
+}
+
+impl StatPresenter
+{
+    pub fn new(_w : Box<dyn std::io::Write>, mode : StatMode) -> StatPresenter
+    {
+        StatPresenter { writer : _w, mode, files : Vec::new() }
+    }
+
+    /// Adds stat of the compared pair computed from its line edit script
+    pub fn add_script(&mut self, _old : &str, _new : &str, script : &[LineCell])
+    {
+        self.files.push(FileStat::from_script(_old, _new, script));
+    }
+
+    #[allow(dead_code)]
+    pub fn files(&self) -> &[FileStat]
+    {
+        &self.files
+    }
+
+    fn write_stat(&mut self) -> std::result::Result<usize, std::io::Error>
+    {
+        let name_width   = self.files.iter().map(|f| f.name.chars().count()).max().unwrap_or(0);
+        let max_changes  = self.files.iter().map(|f| f.changes()).max().unwrap_or(0);
+        let number_width = max_changes.to_string().len();
+
+        //" name | 12 " prefix, the rest of the line is for the bar
+        let bar_width = max(STAT_WIDTH.saturating_sub(name_width + number_width + 5), 10);
+
+        let mut bytes_written = 0;
+        for f in &self.files
+        {
+            let (plus, minus) = scale(f.insertions, f.deletions, max_changes, bar_width);
+            let line = format!(" {:<nw$} | {:>cw$} {}{}", f.name, f.changes(), "+".repeat(plus), "-".repeat(minus),
+                               nw = name_width, cw = number_width);
+            bytes_written += self.writer.write(format!("{}\n", line.trim_end()).as_bytes())?;
+        }
+
+        Ok(bytes_written)
+    }
+
+    fn write_numstat(&mut self) -> std::result::Result<usize, std::io::Error>
+    {
+        let mut bytes_written = 0;
+        for f in &self.files
+        {
+            bytes_written += self.writer.write(format!("{}\t{}\t{}\n", f.insertions, f.deletions, f.name).as_bytes())?;
+        }
+
+        Ok(bytes_written)
+    }
+
+    fn write_total(&mut self) -> std::result::Result<usize, std::io::Error>
+    {
+        let line = total_line(&self.files);
+        self.writer.write(format!("{}\n", line).as_bytes())
+    }
+}
+
+impl Presenter for StatPresenter
+{
+    fn header(&mut self, _old : &str, _new : &str) -> std::result::Result<usize, std::io::Error>
+    {
+        Ok(0)
+    }
+
+    fn present(&mut self, _old : &str, _new : &str, _old_line : Option<u32>, _new_line : Option<u32>, result : &[DiffCell]) -> std::result::Result<usize, std::io::Error>
+    {
+        let changed = !result.is_empty();
+        self.files.push(FileStat {
+            name       : display_name(_old, _new),
+            insertions : changed as usize,
+            deletions  : changed as usize,
+        });
+
+        Ok(0)
+    }
+
+    fn present_moved(&mut self, _line : &str, _line_num : u32, _other_line_num : u32, _source : bool) -> std::result::Result<usize, std::io::Error>
+    {
+        Ok(0)
+    }
+
+    fn present_script(&mut self, _old : &str, _new : &str, script : &[LineCell]) -> bool
+    {
+        self.add_script(_old, _new, script);
+        true
+    }
+
+    fn present_message(&mut self, text : &str) -> std::result::Result<usize, std::io::Error>
+    {
+        self.writer.write(format!("{}\n", text).as_bytes())
+    }
+
+    fn footer(&mut self) -> std::result::Result<usize, std::io::Error>
+    {
+        let mut bytes_written = match self.mode
+        {
+            StatMode::Stat      => self.write_stat()?,
+            StatMode::NumStat   => return self.write_numstat(),
+            StatMode::ShortStat => 0,
+        };
+        bytes_written += self.write_total()?;
+
+        Ok(bytes_written)
+    }
+}
+
+
+///Name of compared pair, "old => new" when they differ
+fn display_name(_old : &str, _new : &str) -> String
+{
+    if _old == _new { _old.to_string() } else { format!("{} => {}", _old, _new) }
+}
+
+
+///Lengths of "+" and "-" parts of histogram bar, scaled down only when the largest
+///change does not fit the width. Non zero part is never scaled to nothing.
+fn scale(insertions : usize, deletions : usize, max_changes : usize, width : usize) -> (usize, usize)
+{
+    if max_changes <= width {
+        return (insertions, deletions);
+    }
+
+    let scaled = |n : usize| if n == 0 { 0 } else { max(1, n * width / max_changes) };
+    let total  = scaled(insertions + deletions);
+    let plus   = scaled(insertions);
+    let minus  = if deletions == 0 { 0 } else { max(1, total.saturating_sub(plus)) };
+
+    (plus, minus)
+}
+
+
+///" 2 files changed, 3 insertions(+), 1 deletion(-)", parts with 0 lines are omitted,
+///unless both are 0
+pub fn total_line(files : &[FileStat]) -> String
+{
+    let insertions : usize = files.iter().map(|f| f.insertions).sum();
+    let deletions  : usize = files.iter().map(|f| f.deletions).sum();
+    let changed = files.iter().filter(|f| f.changes() > 0).count();
+
+    let plural = |n : usize| if n == 1 { "" } else { "s" };
+    let mut line = format!(" {} file{} changed", changed, plural(changed));
+    if insertions > 0 || deletions == 0 {
+        line += &format!(", {} insertion{}(+)", insertions, plural(insertions));
+    }
+    if deletions > 0 || insertions == 0 {
+        line += &format!(", {} deletion{}(-)", deletions, plural(deletions));
+    }
+
+    line
+}
+
+
+#[cfg(test)]
+mod tests
+{
+    use ::diff::stat::*;
+    use ::diff::linediff::LineDiff;
+    use ::diff::presenter::SharedBuffer;
+    use std::rc::Rc;
+    use std::cell::RefCell;
+
+    fn render(mode : StatMode) -> String
+    {
+        let _old = vec!["a", "b", "c", "d"];
+        let _new = vec!["a", "x", "y", "c", "e"];
+        let mut ld = LineDiff::new();
+        let script = ld.get_diff(&_old, &_new);
+
+        let buffer = Rc::new(RefCell::new(Vec::new()));
+        let mut presenter = StatPresenter::new(Box::new(SharedBuffer(buffer.clone())), mode);
+        presenter.add_script("a.txt", "a.txt", &script);
+        presenter.add_script("b.txt", "c.txt", &[]);
+        let _ = presenter.footer();
+
+        let text = String::from_utf8(buffer.borrow().clone()).unwrap();
+        text
+    }
+
+    #[test]
+    fn count_lines()
+    {
+        let _old = vec!["a", "b", "c", "d"];
+        let _new = vec!["a", "x", "y", "c", "e"];
+        let mut ld = LineDiff::new();
+        let stat = FileStat::from_script("a.txt", "a.txt", &ld.get_diff(&_old, &_new));
+
+        assert_eq!(stat, FileStat { name : String::from("a.txt"), insertions : 3, deletions : 2 });
+    }
+
+    #[test]
+    fn stat_modes()
+    {
+        assert_eq!(render(StatMode::Stat), " a.txt          | 5 +++--\n b.txt => c.txt | 0\n 1 file changed, 3 insertions(+), 2 deletions(-)\n");
+        assert_eq!(render(StatMode::NumStat), "3\t2\ta.txt\n0\t0\tb.txt => c.txt\n");
+        assert_eq!(render(StatMode::ShortStat), " 1 file changed, 3 insertions(+), 2 deletions(-)\n");
+    }
+
+    #[test]
+    fn summary_after_messages()
+    {
+        let _old = vec!["a", "b", "c", "d"];
+        let _new = vec!["a", "x", "y", "c", "e"];
+        let mut ld = LineDiff::new();
+        let script = ld.get_diff(&_old, &_new);
+
+        //files and messages come the way directories are compared, the summary is written last
+        let buffer = Rc::new(RefCell::new(Vec::new()));
+        let mut presenter = StatPresenter::new(Box::new(SharedBuffer(buffer.clone())), StatMode::Stat);
+        presenter.present_message("Only in a: b.txt").unwrap();
+        assert!(presenter.present_script("a.txt", "a.txt", &script));
+        presenter.present_message("Only in b: c.txt").unwrap();
+        presenter.footer().unwrap();
+
+        let text = String::from_utf8(buffer.borrow().clone()).unwrap();
+        assert_eq!(text, "Only in a: b.txt\nOnly in b: c.txt\n a.txt | 5 +++--\n 1 file changed, 3 insertions(+), 2 deletions(-)\n");
+    }
+
+    #[test]
+    fn scaled_histogram()
+    {
+        let files = [FileStat { name : String::from("big"), insertions : 300, deletions : 1 }];
+        assert_eq!(total_line(&files), " 1 file changed, 300 insertions(+), 1 deletion(-)");
+
+        assert_eq!(scale(300, 1, 300, 60), (60, 1));
+        assert_eq!(scale(150, 150, 300, 60), (30, 30));
+        assert_eq!(scale(3, 2, 5, 60), (3, 2));
+    }
+}
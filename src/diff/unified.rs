@@ -0,0 +1,147 @@
+//! Unified format presenter, as `diff -u` writes it:
+//!
+//!   --- a.txt
+//!   +++ b.txt
+//!   @@ -1,3 +1,3 @@
+//!    unchanged
+//!   -removed
+//!   +inserted
+//!
+use std;
+use std::io::Write;
+
+use diff::difflib::DiffCell;
+use diff::hunk::{Hunk,HunkRecorder,LineKind};
+use diff::presenter::Presenter;
+
+
+/// Count of unchanged lines around changes, when not specified
+pub const DEFAULT_CONTEXT : usize = 3;
+
+/// Marker following the last line of a side which has no line terminator
+pub const NO_NEWLINE : &str = "\\ No newline at end of file\n";
+
+
+pub struct UnifiedPresenter
+{
+    writer   : Box<dyn std::io::Write>,
+    context  : usize,
+    recorder : HunkRecorder,
+}
+
+impl UnifiedPresenter
+{
+    pub fn new(_w : Box<dyn std::io::Write>, context : usize) -> UnifiedPresenter
+    {
+        UnifiedPresenter { writer : _w, context, recorder : HunkRecorder::new() }
+    }
+}
+
+impl Presenter for UnifiedPresenter
+{
+    fn header(&mut self, _old : &str, _new : &str) -> std::result::Result<usize, std::io::Error>
+    {
+        self.writer.write(format!("--- {}\n+++ {}\n", _old, _new).as_bytes())
+    }
+
+    fn present(&mut self, _old : &str, _new : &str, old_line : Option<u32>, new_line : Option<u32>, result : &[DiffCell]) -> std::result::Result<usize, std::io::Error>
+    {
+        self.recorder.record(_old, _new, old_line, new_line, result);
+        Ok(0)
+    }
+
+    fn present_moved(&mut self, line : &str, _line_num : u32, _other_line_num : u32, source : bool) -> std::result::Result<usize, std::io::Error>
+    {
+        self.recorder.record_moved(line, source);
+        Ok(0)
+    }
+
+    fn present_unchanged(&mut self, line : &str, _old_line : u32, _new_line : u32) -> std::result::Result<usize, std::io::Error>
+    {
+        self.recorder.record_unchanged(line);
+        Ok(0)
+    }
+
+    fn set_no_newline(&mut self, _old : bool, _new : bool)
+    {
+        self.recorder.set_no_newline(_old, _new);
+    }
+
+    fn present_message(&mut self, text : &str) -> std::result::Result<usize, std::io::Error>
+    {
+        self.writer.write(format!("{}\n", text).as_bytes())
+    }
+
+    fn footer(&mut self) -> std::result::Result<usize, std::io::Error>
+    {
+        let mut bytes_written = 0;
+        for hunk in self.recorder.take_hunks(self.context)
+        {
+            bytes_written += self.writer.write(format_hunk(&hunk).as_bytes())?;
+        }
+        self.writer.flush()?;
+
+        Ok(bytes_written)
+    }
+}
+
+
+/// Hunk in unified format: "@@" header followed by its lines and markers of missing newline at the end
+pub fn format_hunk(hunk : &Hunk) -> String
+{
+    let mut out = format!("@@ -{} +{} @@\n", range(hunk.old_start, hunk.old_count), range(hunk.new_start, hunk.new_count));
+    let old_last = hunk.lines.iter().rposition(|l| l.kind != LineKind::Insert);
+    let new_last = hunk.lines.iter().rposition(|l| l.kind != LineKind::Remove);
+    for (i, line) in hunk.lines.iter().enumerate()
+    {
+        let marker = match line.kind { LineKind::Context => ' ', LineKind::Remove => '-', LineKind::Insert => '+' };
+        out += &format!("{}{}\n", marker, line.text);
+        if (hunk.old_no_newline && old_last == Some(i)) || (hunk.new_no_newline && new_last == Some(i)) {
+            out += NO_NEWLINE;
+        }
+    }
+
+    out
+}
+
+
+///Range of hunk header: 1 based first line and count, count is omitted when it is 1,
+///empty range starts at the line before it
+fn range(start : usize, count : usize) -> String
+{
+    match count
+    {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, count),
+    }
+}
+
+
+#[cfg(test)]
+mod tests
+{
+    use std::rc::Rc;
+    use std::cell::RefCell;
+    use ::diff::unified::*;
+    use ::diff::presenter::SharedBuffer;
+
+    #[test]
+    fn unified_format()
+    {
+        let buffer = Rc::new(RefCell::new(Vec::new()));
+        let mut presenter = UnifiedPresenter::new(Box::new(SharedBuffer(buffer.clone())), 1);
+
+        presenter.header("a.txt", "b.txt").unwrap();
+        presenter.present_unchanged("one", 1, 1).unwrap();
+        presenter.present("two", "2", Some(2), Some(2), &[]).unwrap();
+        presenter.present_unchanged("three", 3, 3).unwrap();
+        presenter.present_unchanged("four", 4, 4).unwrap();
+        presenter.present_unchanged("five", 5, 5).unwrap();
+        presenter.present("", "six", None, Some(6), &[]).unwrap();
+        presenter.footer().unwrap();
+
+        let out = String::from_utf8(buffer.borrow().clone()).unwrap();
+        assert_eq!(out, "--- a.txt\n+++ b.txt\n@@ -1,3 +1,3 @@\n one\n-two\n+2\n three\n@@ -5 +5,2 @@\n five\n+six\n");
+    }
+}
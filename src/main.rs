@@ -1,23 +1,66 @@
 
-#[allow(dead_code)]
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
 
+#[allow(dead_code)]
 use std::env;
 use std::collections::HashMap;
-use std::io::{BufReader,BufRead};
+use std::cmp::max;
+use std::io::{BufReader,BufRead,Read};
 use std::fs::File;
+use std::path::Path;
+use std::time::{Duration,Instant};
 
 
 mod diff;
-use diff::presenter::DiffPresenter;
-use diff::difflib::StringDiff;
-
-const ARG_COUNT  : usize = 4;
-
-
-const STR1   : &'static str = "-s1"; //string   _from_ 
-const STR2   : &'static str = "-s2"; //string   _to_
-const FILE1  : &'static str = "-f1"; //file     _from_
-const FILE2  : &'static str = "-f2"; //file     _to_
+use diff::presenter::{Presenter,DiffPresenter};
+use diff::html::{HtmlPresenter,HtmlView};
+use diff::json::{JsonPresenter,JsonMode};
+use diff::stat::{StatPresenter,StatMode};
+use diff::unified::{UnifiedPresenter,DEFAULT_CONTEXT};
+use diff::context::ContextPresenter;
+use diff::ed::{EdPresenter,EdFormat};
+use diff::patch::{self as patch_parser,FilePatch};
+use diff::apply::{self,HunkOutcome,DEFAULT_FUZZ};
+use diff::unified::format_hunk;
+use diff::difflib::{StringDiff,DiffCell,DiffOperation,full_replace};
+use diff::linediff::{self,LineCell};
+use diff::moves;
+use diff::brief::{self,Entry};
+use diff::parallel;
+
+
+const STR1   : &str = "-s1"; //string   _from_ 
+const STR2   : &str = "-s2"; //string   _to_
+const FILE1  : &str = "-f1"; //file     _from_
+const FILE2  : &str = "-f2"; //file     _to_
+const MOVED  : &str = "--moved"; //detect moved blocks of lines
+const HTML   : &str = "--html";  //HTML report instead of the built-in output
+const SIDE_BY_SIDE : &str = "--side-by-side"; //side-by-side view of HTML report
+const JSON   : &str = "--json";   //single JSON document
+const NDJSON : &str = "--ndjson"; //newline-delimited JSON, one object per line
+const STAT      : &str = "--stat";      //inserted and deleted lines with histogram
+const NUMSTAT   : &str = "--numstat";   //inserted and deleted lines, machine readable
+const SHORTSTAT : &str = "--shortstat"; //total line of --stat only
+const STDIN  : &str = "-"; //file name standing for standard input
+const UNIFIED       : [&str; 2] = ["-u", "--unified"]; //unified format
+const CONTEXT       : [&str; 2] = ["-c", "--context"]; //context format
+const ED            : [&str; 2] = ["-e", "--ed"];  //ed script
+const RCS           : [&str; 2] = ["-n", "--rcs"]; //RCS format
+const PATCH      : &str = "patch";     //subcommand applying patch to files
+const PATCH_FILE : &str = "-i";        //patch file, standard input by default
+const STRIP      : &str = "-p";        //count of leading path components to strip from file names
+const FUZZ       : &str = "-F";        //max count of ignored context lines of hunk
+const DRY_RUN    : &str = "--dry-run"; //only report what would happen
+const REVERSE    : &str = "-R";        //apply patch in reverse
+const UNIFIED_LINES : &str = "-U"; //unified format with given count of context lines
+const CONTEXT_LINES : &str = "-C"; //context format with given count of context lines
+const BRIEF     : [&str; 2] = ["-q", "--brief"];                   //only report whether inputs differ
+const IDENTICAL : [&str; 2] = ["-s", "--report-identical-files"];  //report identical inputs too
+const JOBS      : &str = "-j"; //count of threads comparing files of directories, regions of large files and changed lines
+const TIMEOUT   : &str = "--timeout"; //seconds comparisons may take, not minimal differences afterwards
+const DIR_BATCH : usize = 16;  //files of directories compared by each thread before they are presented
 
 ///Helper method for debugging
 #[cfg(debug_assertions)]
@@ -33,7 +76,7 @@ fn debug_proc()
 
     let mut sd   = StringDiff::new(); 
     let diff_vec = sd.get_diff(&_old,&_new);
-    if diff_vec.len() == 0 {
+    if diff_vec.is_empty() {
         println!("No difference")
     }
     else {
@@ -44,175 +87,720 @@ fn debug_proc()
 ///Prints help on console
 fn help()
 {
-     println!("");
+     println!();
      println!("Command line options: ");
      println!("{}  :  first  string to be compared",  STR1);
      println!("{}  :  second string to be compared", STR2);
      println!("{}  :  first  file to be compared",    FILE1);
      println!("{}  :  second file to be compared",   FILE2);
+     println!("{} :  show moved blocks of lines with distinct markers", MOVED);
+     println!("{}  :  write self-contained HTML report", HTML);
+     println!("{} :  side-by-side view of HTML report, inline by default", SIDE_BY_SIDE);
+     println!("{}  :  write single JSON document", JSON);
+     println!("{} :  write newline-delimited JSON, one object per line", NDJSON);
+     println!("{}  :  show count of inserted and deleted lines with histogram", STAT);
+     println!("{} :  show count of inserted and deleted lines, tab separated", NUMSTAT);
+     println!("{} :  show total count of changed files, inserted and deleted lines only", SHORTSTAT);
+     println!("{}, {} :  write unified format with {} lines of context", UNIFIED[0], UNIFIED[1], DEFAULT_CONTEXT);
+     println!("{} N :  write unified format with N lines of context", UNIFIED_LINES);
+     println!("{}, {} :  write context format with {} lines of context", CONTEXT[0], CONTEXT[1], DEFAULT_CONTEXT);
+     println!("{} N :  write context format with N lines of context", CONTEXT_LINES);
+     println!("{}, {} :  write ed script changing the first file into the second one", ED[0], ED[1]);
+     println!("{}, {} :  write RCS format", RCS[0], RCS[1]);
+     println!("{}, {} :  only report whether inputs differ, exit code is 1 when they do", BRIEF[0], BRIEF[1]);
+     println!("{}, {} :  report when inputs are identical", IDENTICAL[0], IDENTICAL[1]);
+     println!("{} N :  compare on N threads, 0 uses all cores, output is the same as with 1 thread", JOBS);
+     println!("{} SECS :  limit time of comparisons, lines not compared in time are shown as removed and inserted or updated whole", TIMEOUT);
      println!("-h   : print help");     
-     println!("");
+     println!();
      println!("Note : -str and -f options are mutually exclusive. You have to specify one _or_ another in both parameters");
-     println!("");
+     println!("       -f options accept directories, they are compared recursively (not into HTML or JSON document);");
+     println!("       file compared with directory is compared with the file of the same name in it");
+     println!("       {} as file name reads standard input, pipes and /dev/fd/N paths are read as they come", STDIN);
+     println!();
+     println!("Applying patch: {} [options]", PATCH);
+     println!("{} FILE :  patch to apply, standard input by default", PATCH_FILE);
+     println!("{} N :  strip N leading components from file names", STRIP);
+     println!("{} N :  ignore at most N context lines at hunk edges, {} by default", FUZZ, DEFAULT_FUZZ);
+     println!("{} :  only report what would happen, change no file", DRY_RUN);
+     println!("{}   :  apply patch in reverse", REVERSE);
+     println!("Hunks which can not be applied are saved to FILE.rej, exit code is 1 then");
+     println!();
      println!("Example: ");
      println!("Diff.exe {} 'Hello!' {} 'Hola!'", STR1, STR2);
-     println!("");
+     println!("Diff.exe {} {} 1 {} fix.patch", PATCH, STRIP, PATCH_FILE);
+     println!();
      
 }
 
 fn error(error : &str)
 {
-    println!("");
+    println!();
     println!("== ERROR == ");
     println!("{}", error);
    
 }
 
+///Line without its terminator, "\n" or "\r\n"
 fn trim_newline(text : &mut str) -> &str
 {   
-   let matches: &[_] = &['\n', '\r'];
-   text.trim_matches(matches)
+   let text = text.strip_suffix('\n').unwrap_or(text);
+   text.strip_suffix('\r').unwrap_or(text)
+}
+
+///Opens file for sequential reading, STDIN name opens standard input.
+///Inputs are never seeked, so pipes and process substitutions (/dev/fd/N) work as regular files
+fn open_input(name : &str) -> std::io::Result<Box<dyn Read>>
+{
+    if name == STDIN {
+        Ok(Box::new(std::io::stdin()))
+    }
+    else {
+        Ok(Box::new(File::open(name)?))
+    }
+}
+
+///Reads all lines of the input, without line terminators. 
+///Returns also whether the last line has no line terminator
+fn read_lines<R : Read>(input : R) -> std::io::Result<(Vec<String>, bool)>
+{
+    let mut lines  = Vec::new();
+    let mut line   = String::with_capacity(512);
+    let mut bufer  = BufReader::new(input);
+    let mut no_newline = false;
+
+    while bufer.read_line(&mut line)? > 0
+    {
+        no_newline = !line.ends_with('\n');
+        lines.push(trim_newline(&mut line).to_string());
+        line.clear();
+    }
+
+    Ok((lines, no_newline))
+}
+
+///Line of the presented script
+enum ScriptRow<'a>
+{
+    Unchanged { line : &'a str, old_line : u32, new_line : u32 },
+    Moved     { line : &'a str, line_num : u32, other_line_num : u32, source : bool },
+    Changed   { old : &'a str, new : &'a str, old_line : Option<u32>, new_line : Option<u32> },
+}
+
+///Walks line edit script row by row: removed block directly followed by inserted one 
+///gives pairs of changed lines, moved blocks are given line by line at both places 
+fn walk_script<'a, F : FnMut(ScriptRow<'a>)>(lines1 : &'a [String], lines2 : &'a [String], script : &[LineCell], mut f : F)
+{
+    let mut i = 0;
+    while i < script.len()
+    {
+        let cell = &script[i];
+        let (removed, inserted) = match cell.operation
+        {
+            DiffOperation::Remove => {
+                match script.get(i + 1) {
+                    Some(next) if next.operation == DiffOperation::Insert => { i += 1; (Some(cell), Some(next)) },
+                    _ => (Some(cell), None),
+                }
+            },
+            DiffOperation::Insert => (None, Some(cell)),
+            DiffOperation::Moved { from, to } => {
+                let source = cell.is_move_source();
+                for k in 0 .. cell.count
+                {
+                    let (line, line_num, other_line_num) = if source {
+                        (lines1[cell.old_start + k].as_str(), cell.old_start + k + 1, to + k + 1)
+                    } 
+                    else {
+                        (lines2[cell.new_start + k].as_str(), cell.new_start + k + 1, from + k + 1)
+                    };
+                    f(ScriptRow::Moved { line, line_num : line_num as u32, other_line_num : other_line_num as u32, source });
+                }
+                (None, None)
+            },
+            _ => {
+                for k in 0 .. cell.count
+                {
+                    f(ScriptRow::Unchanged { line : &lines1[cell.old_start + k], old_line : (cell.old_start + k + 1) as u32, new_line : (cell.new_start + k + 1) as u32 });
+                }
+                (None, None)
+            },
+        };
+
+        let rem_count = removed.map_or(0, |c| c.count);
+        let ins_count = inserted.map_or(0, |c| c.count);
+        let old_start = removed.map_or(0, |c| c.old_start);
+        let new_start = inserted.map_or(0, |c| c.new_start);
+        for k in 0 .. max(rem_count, ins_count)
+        {
+            f(ScriptRow::Changed {
+                old      : if k < rem_count { lines1[old_start + k].as_str() } else { "" },
+                new      : if k < ins_count { lines2[new_start + k].as_str() } else { "" },
+                old_line : if k < rem_count { Some((old_start + k + 1) as u32) } else { None },
+                new_line : if k < ins_count { Some((new_start + k + 1) as u32) } else { None },
+            });
+        }
+
+        i += 1;
+    }
+}
+
+///Character differences of changed lines. Lines rewritten by more than a half 
+///are reported as replaced whole, without comparing them to the end
+fn changed_line_diff(sd : &mut StringDiff, line1 : &str, line2 : &str, result : &mut Vec<DiffCell>)
+{
+    let bound = max(line1.chars().count(), line2.chars().count()) as u32 / 2;
+    if !sd.get_diff_bounded_into(line1, line2, bound, result) {
+        *result = full_replace(line1, line2);
+    }
+}
+
+///Character comparison engine, which stops comparing at the deadline
+fn string_diff(deadline : Option<Instant>) -> StringDiff
+{
+    let mut sd = StringDiff::new();
+    sd.set_deadline(deadline);
+    sd
+}
+
+///Presents line edit script: changed line pairs are presented as character level update.
+///With more than one thread, changed lines are compared in parallel first, 
+///each thread with its own StringDiff, and presented in the same order afterwards
+fn present_lines(presenter : &mut dyn Presenter, lines1 : &[String], lines2 : &[String], script : &[LineCell], threads : usize, deadline : Option<Instant>)
+{
+    let mut diffs = Vec::new().into_iter();
+    if threads > 1
+    {
+        let mut pairs = Vec::new();
+        walk_script(lines1, lines2, script, |row| if let ScriptRow::Changed { old, new, .. } = row { pairs.push((old, new)); });
+        diffs = parallel::map(&pairs, threads, || string_diff(deadline), |sd, &(line1, line2)| {
+            let mut result = Vec::new();
+            changed_line_diff(sd, line1, line2, &mut result);
+            result
+        }).into_iter();
+    }
+
+    let mut sd_file = string_diff(deadline);
+    let mut result  = Vec::new();
+    walk_script(lines1, lines2, script, |row| {
+        let _ = match row
+        {
+            ScriptRow::Unchanged { line, old_line, new_line } => presenter.present_unchanged(line, old_line, new_line),
+            ScriptRow::Moved { line, line_num, other_line_num, source } => presenter.present_moved(line, line_num, other_line_num, source),
+            ScriptRow::Changed { old, new, old_line, new_line } => {
+                //Get difference
+                match diffs.next() {
+                    Some(diff) => result = diff,
+                    None => changed_line_diff(&mut sd_file, old, new, &mut result),
+                }
+
+                //Present
+                presenter.present(old, new, old_line, new_line, &result)
+            },
+        };
+    });
+}
+
+///Requested diffstat mode, if any
+fn stat_mode(arguments : &HashMap<String,String>) -> Option<StatMode>
+{
+    if arguments.contains_key(STAT) {
+        Some(StatMode::Stat)
+    }
+    else if arguments.contains_key(NUMSTAT) {
+        Some(StatMode::NumStat)
+    }
+    else if arguments.contains_key(SHORTSTAT) {
+        Some(StatMode::ShortStat)
+    }
+    else {
+        None
+    }
+}
+
+///True when any of the switch spellings is present
+fn has_switch(arguments : &HashMap<String,String>, spellings : &[&str]) -> bool
+{
+    spellings.iter().any(|s| arguments.contains_key(*s))
+}
+
+///Brief comparison: prints only whether inputs differ, no difference is computed.
+///Returns true when inputs differ
+fn run_brief(arguments : &HashMap<String,String>, threads : usize) -> bool
+{
+    let report_identical = has_switch(arguments, &IDENTICAL);
+
+    if let (Some(_old), Some(_new)) = (arguments.get(STR1), arguments.get(STR2))
+    {
+        if _old != _new {
+            println!("Strings \"{}\" and \"{}\" differ", _old, _new);
+        }
+        else if report_identical {
+            println!("Strings \"{}\" and \"{}\" are identical", _old, _new);
+        }
+        _old != _new
+    }
+    else if let (Some(file1_name), Some(file2_name)) = (arguments.get(FILE1), arguments.get(FILE2))
+    {
+        //standard input can only be compared as a stream
+        let result = if file1_name == STDIN || file2_name == STDIN {
+            open_input(file1_name)
+                .and_then(|f1| Ok((f1, open_input(file2_name)?)))
+                .and_then(|(f1, f2)| brief::streams_differ(f1, f2))
+                .map(|differ| {
+                    let (p1, p2) = (Path::new(file1_name).to_path_buf(), Path::new(file2_name).to_path_buf());
+                    vec![if differ { brief::BriefResult::Differ(p1, p2) } else { brief::BriefResult::Identical(p1, p2) }]
+                })
+        }
+        else {
+            brief::compare_parallel(Path::new(file1_name), Path::new(file2_name), threads)
+        };
+
+        match result
+        {
+            Ok(result) => {
+                for r in result.iter().filter(|r| report_identical || r.is_difference())
+                {
+                    println!("{}", r);
+                }
+                result.iter().any(|r| r.is_difference())
+            },
+            Err(e) => {
+                error(&format!("Can not compare {} and {}: {}", file1_name, file2_name, e));
+                false
+            },
+        }
+    }
+    else {
+        error("Incorrect sequence of arguments");
+        help();
+        false
+    }
 }
 
 fn run(arguments : &HashMap<String,String>)
 {
-    let mut opt1 = arguments.get(STR1);
-    let mut opt2 = arguments.get(STR2);
+    if arguments.get(FILE1).is_some_and(|f| f == STDIN) && arguments.get(FILE2).is_some_and(|f| f == STDIN)
+    {
+        error("Standard input can be compared only with a file");
+        return;
+    }
 
-    //Init presenter: current one is a stdout one. 
-    let mut presenter = DiffPresenter::new(Box::new(std::io::stdout()));  
+    let threads = match arguments.get(JOBS).map(|n| n.parse::<usize>())
+    {
+        Some(Ok(0)) => parallel::available_threads(),
+        Some(Ok(n)) => n,
+        Some(Err(_)) => { error("Count of threads has to be a non negative number"); return; },
+        None => 1,
+    };
+
+    //all comparisons share the deadline
+    let deadline = match arguments.get(TIMEOUT).map(|t| t.parse::<f64>())
+    {
+        Some(Ok(secs)) if secs >= 0.0 && secs.is_finite() => Some(Instant::now() + Duration::from_secs_f64(secs)),
+        Some(_) => { error("Timeout has to be a non negative number of seconds"); return; },
+        None => None,
+    };
+
+    if has_switch(arguments, &BRIEF)
+    {
+        if run_brief(arguments, threads) {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let context = match arguments.get(UNIFIED_LINES).or(arguments.get(CONTEXT_LINES)).map(|n| n.parse::<usize>())
+    {
+        Some(Ok(n)) => n,
+        Some(Err(_)) => { error("Count of context lines has to be a non negative number"); return; },
+        None => DEFAULT_CONTEXT,
+    };
+
+    //Init presenter: built-in, HTML, JSON, unified, context, ed, RCS or summarizing one, all write to stdout. 
+    let mut presenter : Box<dyn Presenter> = if let Some(mode) = stat_mode(arguments) {
+        Box::new(StatPresenter::new(Box::new(std::io::stdout()), mode))
+    }
+    else if has_switch(arguments, &UNIFIED) || arguments.contains_key(UNIFIED_LINES) {
+        Box::new(UnifiedPresenter::new(Box::new(std::io::stdout()), context))
+    }
+    else if has_switch(arguments, &CONTEXT) || arguments.contains_key(CONTEXT_LINES) {
+        Box::new(ContextPresenter::new(Box::new(std::io::stdout()), context))
+    }
+    else if has_switch(arguments, &ED) {
+        Box::new(EdPresenter::new(Box::new(std::io::stdout()), EdFormat::Ed))
+    }
+    else if has_switch(arguments, &RCS) {
+        Box::new(EdPresenter::new(Box::new(std::io::stdout()), EdFormat::Rcs))
+    }
+    else if arguments.contains_key(HTML) {
+        let view = if arguments.contains_key(SIDE_BY_SIDE) { HtmlView::SideBySide } else { HtmlView::Inline };
+        Box::new(HtmlPresenter::new(Box::new(std::io::stdout()), view))
+    }
+    else if arguments.contains_key(JSON) {
+        Box::new(JsonPresenter::new(Box::new(std::io::stdout()), JsonMode::Document))
+    }
+    else if arguments.contains_key(NDJSON) {
+        Box::new(JsonPresenter::new(Box::new(std::io::stdout()), JsonMode::Lines))
+    }
+    else {
+        Box::new(DiffPresenter::new(Box::new(std::io::stdout())))
+    };
 
     //Comparing strings  
-    if  opt1.is_some() && opt2.is_some()
+    if let (Some(_old), Some(_new)) = (arguments.get(STR1), arguments.get(STR2))
     {       
-        let mut sd_string = StringDiff::new();  
-        let _old = opt1.unwrap();
-        let _new = opt2.unwrap();
+        let mut sd_string = string_diff(deadline);  
 
         //Get difference 
-        let diff = sd_string.get_diff(&_old, &_new);
+        let diff = sd_string.get_diff(_old, _new);
 
         //Present
-        let _ = presenter.header(&_old, &_new);
-        let _ = presenter.present(&_old, &_new, 0, &diff);
+        let _ = presenter.header(_old, _new);
+        let _ = presenter.present(_old, _new, None, None, &diff);
+        let _ = presenter.footer();
+
+        if diff.is_empty() && has_switch(arguments, &IDENTICAL) {
+            println!("Strings \"{}\" and \"{}\" are identical", _old, _new);
+        }
     }
     //Comparing files
-    else 
+    else if let (Some(file1_name), Some(file2_name)) = (arguments.get(FILE1), arguments.get(FILE2))
     {
-        opt1 = arguments.get(FILE1);
-        opt2 = arguments.get(FILE2);
-        if  opt1.is_some() && opt2.is_some()
+        let (path1, path2) = (Path::new(file1_name), Path::new(file2_name));
+        if path1.is_dir() && path2.is_dir()
         {
-            let mut sd_file  = StringDiff::new();  
-            
-            let file1_name = opt1.unwrap();
-            let file2_name = opt2.unwrap();
-             
-            let file1 = File::open(file1_name);
-            if file1.is_err() 
-            {
-                error(&format!("Can not open file {}", file1_name));
-                return;
-            }
-            let file2 = File::open(file2_name);
-            if file2.is_err()
+            if arguments.contains_key(HTML) || arguments.contains_key(JSON)
             {
-                error(&format!("Can not open file {}",  file2_name));
+                error("Directories can not be compared into single HTML or JSON document");
                 return;
             }
+            run_dirs(arguments, presenter.as_mut(), path1, path2, threads, deadline);
+            return;
+        }
 
-           
+        if (file1_name == STDIN && path2.is_dir()) || (file2_name == STDIN && path1.is_dir())
+        {
+            error("Standard input can not be compared with a directory");
+            return;
+        }
+
+        //file compared with directory is compared with the file of the same name in it
+        let (path1, path2) = brief::resolve_paths(path1, path2);
+        let (file1_name, file2_name) = (&path1.display().to_string(), &path2.display().to_string());
+
+        let moved = arguments.contains_key(MOVED) && stat_mode(arguments).is_none();
+        let ComparedFiles { lines1, lines2, script, no_newline1, no_newline2 } = match diff_files(file1_name, file2_name, moved, threads, deadline) {
+            Ok(diff) => diff,
+            Err(e) => { error(&e); return; },
+        };
+        let identical = script.iter().all(|c| c.operation == DiffOperation::None);
+
+        //Summary is computed from the line script, changed lines are not compared by characters
+        if !presenter.present_script(file1_name, file2_name, &script)
+        {
+            presenter.set_no_newline(no_newline1, no_newline2);
             let _ = presenter.header(file1_name, file2_name);
-         
-            let mut line_num = 1;
-
-            //Create buffers for reading files line-by-line
-            let mut line1   = String::with_capacity(512);
-            let mut line2   = String::with_capacity(512);
-            let mut bufer1  = BufReader::new(file1.unwrap()); 
-            let mut bufer2  = BufReader::new(file2.unwrap());
-            let mut res1    = bufer1.read_line(&mut line1).unwrap();
-            let mut res2    = bufer2.read_line(&mut line2).unwrap();
-           
-
-            
-            while res1 > 0 && res2 > 0
-            {               
-                {
-                    let line1_trimed = trim_newline(&mut line1); 
-                    let line2_trimed = trim_newline(&mut line2); 
+            present_lines(presenter.as_mut(), &lines1, &lines2, &script, threads, deadline);
+        }
+        let _ = presenter.footer();
 
-                    //Get difference 
-                    let result = sd_file.get_diff(&line1_trimed, &line2_trimed);  
+        if identical && has_switch(arguments, &IDENTICAL) {
+            println!("Files {} and {} are identical", file1_name, file2_name);
+        }
+    }
+    else {
+        error("Incorrect sequence of arguments");
+        help();
+    }
 
-                    //Present
-                    let _ = presenter.present(&line1_trimed, &line2_trimed, line_num, &result); 
-                }
+}
 
-                line1.clear();
-                line2.clear() ;
-                res1 = bufer1.read_line(&mut line1).unwrap();
-                res2 = bufer2.read_line(&mut line2).unwrap();                    
-                
-                line_num = line_num + 1;             
-            }
+///Lines of 2 compared files and their line edit script
+struct ComparedFiles
+{
+    lines1 : Vec<String>,
+    lines2 : Vec<String>,
+    script : Vec<LineCell>,
+    no_newline1 : bool, //last line of the file has no line terminator
+    no_newline2 : bool,
+}
 
-            //If first file is longer than second 
-            while res1 > 0
-            {               
-                {
-                   let line1_trimed = trim_newline(&mut line1); 
-                   let result = sd_file.get_diff(&line1_trimed, "");    
-                   let _ = presenter.present(&line1_trimed, "", line_num, &result);     
-                }
+///Reads lines of both files and computes their line edit script, large files are compared on `threads` threads.
+///Ambiguous blocks are compacted to natural boundaries and moved blocks are detected on request
+fn diff_files(file1_name : &str, file2_name : &str, moved : bool, threads : usize, deadline : Option<Instant>) -> Result<ComparedFiles, String>
+{
+    let file1 = open_input(file1_name).map_err(|_| format!("Can not open file {}", file1_name))?;
+    let file2 = open_input(file2_name).map_err(|_| format!("Can not open file {}", file2_name))?;
+    let (mut lines1, no_newline1) = read_lines(file1).map_err(|_| format!("Can not read file {}", file1_name))?;
+    let (mut lines2, no_newline2) = read_lines(file2).map_err(|_| format!("Can not read file {}", file2_name))?;
+
+    //as in diff, last line without line terminator differs from the same text with it
+    mark_no_newline(&mut lines1, no_newline1, true);
+    mark_no_newline(&mut lines2, no_newline2, true);
+    let mut script = linediff::get_diff_parallel(&lines1, &lines2, threads, deadline);
+    if moved {
+        moves::detect_moves(&mut script, &lines1, &lines2);
+    }
+    mark_no_newline(&mut lines1, no_newline1, false);
+    mark_no_newline(&mut lines2, no_newline2, false);
 
-                line1.clear();
-                res1 = bufer1.read_line(&mut line1).unwrap();
-                  
-                line_num = line_num + 1;
+    Ok(ComparedFiles { lines1, lines2, script, no_newline1, no_newline2 })
+}
+
+///Adds (or removes) line feed to the last line without line terminator, so it is compared as different line
+fn mark_no_newline(lines : &mut [String], no_newline : bool, mark : bool)
+{
+    if let Some(last) = lines.last_mut().filter(|_| no_newline)
+    {
+        if mark {
+            last.push('\n');
+        }
+        else {
+            last.pop();
+        }
+    }
+}
+
+///Compares directories recursively, as diff -r: pairs of files are compared on `threads` threads and
+///presented in order of their names, files present at one side only are reported
+fn run_dirs(arguments : &HashMap<String,String>, presenter : &mut dyn Presenter, dir1 : &Path, dir2 : &Path, threads : usize, deadline : Option<Instant>)
+{
+    let entries = match brief::dir_entries(dir1, dir2) {
+        Ok(entries) => entries,
+        Err(e) => { error(&format!("Can not compare {} and {}: {}", dir1.display(), dir2.display(), e)); return; },
+    };
+
+    let summary = stat_mode(arguments).is_some();
+    let moved = arguments.contains_key(MOVED) && !summary;
+
+    //files of a batch are kept in memory until they are presented
+    for batch in entries.chunks(max(threads, 1) * DIR_BATCH)
+    {
+        let diffs = parallel::map(batch, threads, || (), |_, entry| match *entry {
+            Entry::Files(ref p1, ref p2) => Some(diff_files(&p1.display().to_string(), &p2.display().to_string(), moved, 1, deadline)),
+            Entry::Done(_) => None,
+        });
+
+        for (entry, diff) in batch.iter().zip(diffs)
+        {
+            match (entry, diff)
+            {
+                (Entry::Done(result), _) => { let _ = presenter.present_message(&result.to_string()); },
+                (Entry::Files(p1, p2), Some(Ok(ComparedFiles { lines1, lines2, script, no_newline1, no_newline2 }))) => {
+                    let (name1, name2) = (p1.display().to_string(), p2.display().to_string());
+                    let identical = script.iter().all(|c| c.operation == DiffOperation::None);
+                    if identical {
+                        if has_switch(arguments, &IDENTICAL) {
+                            let _ = presenter.present_message(&format!("Files {} and {} are identical", name1, name2));
+                        }
+                    }
+                    else if !presenter.present_script(&name1, &name2, &script) {
+                        presenter.set_no_newline(no_newline1, no_newline2);
+                        let _ = presenter.header(&name1, &name2);
+                        present_lines(presenter, &lines1, &lines2, &script, threads, deadline);
+                        let _ = presenter.footer();
+                    }
+                },
+                (_, Some(Err(e))) => error(&e),
+                _ => {},
             }
-            
+        }
+    }
 
-            //If second file is longer than first
-            while res2 > 0
-            {       
-                {
-                    let line2_trimed = trim_newline(&mut line2); 
-                    let result = sd_file.get_diff("", &line2_trimed);    
-                    let _ = presenter.present("", &line2_trimed, line_num, &result); 
-                }   
+    //summary of all files is written once, at the end
+    if summary {
+        let _ = presenter.footer();
+    }
+}
+
+///File name of the patch without `strip` leading components, the last one is always kept
+fn strip_path(name : &str, strip : usize) -> String
+{
+    let components : Vec<&str> = name.split('/').collect();
+    components[std::cmp::min(strip, components.len() - 1) ..].join("/")
+}
+
+#[cfg(unix)]
+fn set_mode(name : &str, mode : u32) -> std::io::Result<()>
+{
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(name, std::fs::Permissions::from_mode(mode & 0o7777))
+}
+
+#[cfg(not(unix))]
+fn set_mode(_name : &str, _mode : u32) -> std::io::Result<()>
+{
+    Ok(())
+}
+
+///Applies single file patch, reports outcome of every hunk. Returns true when all hunks are applied
+fn apply_file_patch(arguments : &HashMap<String,String>, patch : &FilePatch, strip : usize, fuzz : usize) -> bool
+{
+    let dry_run = arguments.contains_key(DRY_RUN);
+
+    //names of git rename and copy headers have no prefixes to strip
+    let strip = if patch.rename || patch.copy { 0 } else { strip };
+    let old_name = patch.old_name.as_ref().map(|n| strip_path(n, strip));
+    let new_name = patch.new_name.as_ref().map(|n| strip_path(n, strip));
+
+    let source = match (&old_name, &new_name) {
+        (Some(old), Some(new)) => if Path::new(old).exists() || !Path::new(new).exists() { old.clone() } else { new.clone() },
+        (Some(old), None) => old.clone(),
+        (None, Some(new)) => new.clone(),
+        (None, None) => { error("Patch without file names"); return false; },
+    };
+    //file is patched in place, unless it is renamed, copied or created
+    let target = match new_name {
+        Some(ref new) if patch.rename || patch.copy || old_name.is_none() => new.clone(),
+        _ => source.clone(),
+    };
+
+    let action = if dry_run { "checking" } else { "patching" };
+    if patch.rename && source != target {
+        println!("{} file {} (renamed from {})", action, target, source);
+    }
+    else {
+        println!("{} file {}", action, target);
+    }
 
-                line2.clear();
-                res2 = bufer2.read_line(&mut line2).unwrap();    
+    if patch.binary
+    {
+        println!("File {}: binary patches are not supported", target);
+        return false;
+    }
+
+    let mut text = String::new();
+    if old_name.is_some()
+    {
+        if let Err(e) = open_input(&source).and_then(|mut input| input.read_to_string(&mut text)) {
+            error(&format!("Can not read file {}: {}", source, e));
+            return false;
+        }
+    }
 
-                line_num = line_num + 1;
-            }              
+    let (patched, result) = apply::apply_text(&text, &patch.hunks, fuzz);
+    for (i, outcome) in result.outcomes.iter().enumerate()
+    {
+        match *outcome
+        {
+            HunkOutcome::Applied { line, offset, fuzz } if offset != 0 || fuzz > 0 => {
+                let with_fuzz = if fuzz > 0 { format!(" with fuzz {}", fuzz) } else { String::new() };
+                let with_offset = if offset != 0 { format!(" (offset {} line{})", offset, if offset.abs() == 1 { "" } else { "s" }) } else { String::new() };
+                println!("Hunk #{} succeeded at {}{}{}.", i + 1, line, with_fuzz, with_offset);
+            },
+            HunkOutcome::Failed => println!("Hunk #{} FAILED at {}.", i + 1, patch.hunks[i].old_start + 1),
+            _ => {},
+        }
+    }
 
+    let failed = result.failed();
+    if !dry_run
+    {
+        let written = if new_name.is_none() && result.lines.is_empty() {
+            std::fs::remove_file(&source)
+        }
+        else if !patch.hunks.is_empty() || source != target {
+            std::fs::write(&target, patched)
         }
         else {
-            error("Incorrect sequence of arguments");
-            help();
+            Ok(())
+        };
+        let written = written
+                        .and_then(|_| if patch.rename && source != target { std::fs::remove_file(&source) } else { Ok(()) })
+                        .and_then(|_| match patch.new_mode { Some(mode) if new_name.is_some() => set_mode(&target, mode), _ => Ok(()) });
+        if let Err(e) = written {
+            error(&format!("Can not write file {}: {}", target, e));
+            return false;
         }
     }
 
+    if !failed.is_empty()
+    {
+        let plural = if patch.hunks.len() == 1 { "" } else { "s" };
+        if dry_run {
+            println!("{} out of {} hunk{} FAILED", failed.len(), patch.hunks.len(), plural);
+        }
+        else {
+            let rej_name = format!("{}.rej", target);
+            println!("{} out of {} hunk{} FAILED -- saving rejects to file {}", failed.len(), patch.hunks.len(), plural, rej_name);
+
+            let mut rejects = format!("--- {}\n+++ {}\n", source, target);
+            for i in &failed
+            {
+                rejects += &format_hunk(&patch.hunks[*i]);
+            }
+            if let Err(e) = std::fs::write(&rej_name, rejects) {
+                error(&format!("Can not write file {}: {}", rej_name, e));
+            }
+        }
+    }
+
+    failed.is_empty()
 }
 
-///Generates arguments hash from provided command line 
+///Applies patch to the files it names. Returns exit code: 0 when all hunks are applied,
+///1 when some failed, 2 when patch can not be read
+fn run_patch(arguments : &HashMap<String,String>) -> i32
+{
+    let number = |key : &str, default : usize| arguments.get(key).map_or(Ok(default), |n| n.parse::<usize>());
+    let (strip, fuzz) = match (number(STRIP, 0), number(FUZZ, DEFAULT_FUZZ)) {
+        (Ok(strip), Ok(fuzz)) => (strip, fuzz),
+        _ => { error(&format!("{} and {} have to be non negative numbers", STRIP, FUZZ)); return 2; },
+    };
+
+    let patch_name = arguments.get(PATCH_FILE).map_or(STDIN, |n| n.as_str());
+    let mut text = String::new();
+    if let Err(e) = open_input(patch_name).and_then(|mut input| input.read_to_string(&mut text)) {
+        error(&format!("Can not read patch {}: {}", patch_name, e));
+        return 2;
+    }
+
+    let patches = match patch_parser::parse(&text) {
+        Ok(patches) => patches,
+        Err(e) => { error(&format!("Malformed patch {}, {}", patch_name, e)); return 2; },
+    };
+    if patches.is_empty() {
+        error(&format!("No patch found in {}", patch_name));
+        return 2;
+    }
+
+    let mut all_applied = true;
+    for patch in &patches
+    {
+        let patch = if arguments.contains_key(REVERSE) { patch.inverted() } else { patch.clone() };
+        all_applied &= apply_file_patch(arguments, &patch, strip, fuzz);
+    }
+
+    if all_applied { 0 } else { 1 }
+}
+
+///Generates arguments hash from provided command line, switches are stored with empty value 
 /// # Arguments
 ///
-///* `args:&Vec<String>` - vector of command line parameters and arguments
-fn hash_from_args(args : &Vec<String>) -> HashMap<String,String>
+///* `args:&[String]` - vector of command line parameters and arguments
+fn hash_from_args(args : &[String]) -> HashMap<String,String>
 {
     let mut arguments: HashMap<String,String> = HashMap::new();
 
     
-    let allowed_args  = vec![STR1, STR2, FILE1, FILE2];
+    let allowed_args      = [STR1, STR2, FILE1, FILE2, UNIFIED_LINES, CONTEXT_LINES, JOBS, TIMEOUT, PATCH_FILE, STRIP, FUZZ];
+    let allowed_switches  = [MOVED, HTML, SIDE_BY_SIDE, JSON, NDJSON, STAT, NUMSTAT, SHORTSTAT,
+                             BRIEF[0], BRIEF[1], IDENTICAL[0], IDENTICAL[1],
+                             UNIFIED[0], UNIFIED[1], CONTEXT[0], CONTEXT[1], ED[0], ED[1], RCS[0], RCS[1],
+                             DRY_RUN, REVERSE];
   
-    for i in 0 .. args.len() - 1
+    let mut i = 0;
+    while i < args.len()
     {    
-        if allowed_args.iter().any(|&x| *x == args[i]) {           
+        if allowed_args.iter().any(|&x| *x == args[i]) && i + 1 < args.len() {           
             arguments.insert(args[i].clone(), args[i + 1].clone());           
+            i += 1;
         }       
+        else if allowed_switches.iter().any(|&x| *x == args[i]) {
+            arguments.insert(args[i].clone(), String::new());
+        }
+        i += 1;
     }
 
     arguments
@@ -225,24 +813,77 @@ fn main()
 
     let cmdline : Vec<String> = env::args().skip(1).collect();
 
+    if cmdline.first().is_some_and(|c| c == PATCH)
+    {
+        std::process::exit(run_patch(&hash_from_args(&cmdline[1 ..])));
+    }
+
     match cmdline.len()
     {
         0 => { error("No argument specified");  help();},
         1 => {
-                if cmdline[1] == "-h"
+                if cmdline[0] == "-h"
                 {
                     help();
                 }
+                else {
+                    error("Incorrect arguments passed");
+                    help();
+                }
              },
-        ARG_COUNT => 
+        _ => 
              {
                 run(&hash_from_args(&cmdline));
              } 
-        _ => {
-                error("Incorrect arguments passed");
-                help();
-             },
 
     }
 
 }
+
+
+#[cfg(test)]
+mod tests
+{
+    use std::rc::Rc;
+    use std::cell::RefCell;
+    use std::fs;
+    use diff::presenter::SharedBuffer;
+    use super::*;
+
+    ///Unified difference of files with given content, as the command line writes it
+    fn unified(text1 : &str, text2 : &str) -> String
+    {
+        let dir = env::temp_dir().join(format!("diff_main_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (name1, name2) = (dir.join("a.txt").display().to_string(), dir.join("b.txt").display().to_string());
+        fs::write(&name1, text1).unwrap();
+        fs::write(&name2, text2).unwrap();
+        let files = diff_files(&name1, &name2, false, 1, None);
+        fs::remove_dir_all(&dir).unwrap();
+        let ComparedFiles { lines1, lines2, script, no_newline1, no_newline2 } = files.unwrap();
+
+        let buffer = Rc::new(RefCell::new(Vec::new()));
+        let mut presenter = UnifiedPresenter::new(Box::new(SharedBuffer(buffer.clone())), DEFAULT_CONTEXT);
+        presenter.set_no_newline(no_newline1, no_newline2);
+        present_lines(&mut presenter, &lines1, &lines2, &script, 1, None);
+        presenter.footer().unwrap();
+
+        let out = buffer.borrow().clone();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn no_newline_at_end_of_file()
+    {
+        //only the line terminator of the last line differs
+        assert_eq!(unified("a\nb", "a\nb\n"), "@@ -1,2 +1,2 @@\n a\n-b\n\\ No newline at end of file\n+b\n");
+        assert_eq!(unified("a\nb\n", "a\nb"), "@@ -1,2 +1,2 @@\n a\n-b\n+b\n\\ No newline at end of file\n");
+
+        //the same last line without terminator at both sides is context
+        assert_eq!(unified("a\nb", "x\nb"), "@@ -1,2 +1,2 @@\n-a\n+x\n b\n\\ No newline at end of file\n");
+        assert_eq!(unified("a\nb", "a\nb"), "");
+
+        //hunk not reaching the end is not marked
+        assert_eq!(unified("a\n1\n2\n3\n4\nb", "x\n1\n2\n3\n4\nb"), "@@ -1,4 +1,4 @@\n-a\n+x\n 1\n 2\n 3\n");
+    }
+}